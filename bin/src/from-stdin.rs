@@ -8,6 +8,12 @@ struct Args {
 
     #[argh(switch, description = "use debug printing rather than JSON")]
     debug: bool,
+
+    #[argh(option, description = "key id to sign requests with")]
+    key_id: Option<url::Url>,
+
+    #[argh(option, description = "PEM private key file to sign requests with")]
+    private_key: Option<std::path::PathBuf>,
 }
 
 #[tokio::main]
@@ -16,6 +22,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args: Args = argh::from_env();
 
+    let signer = match (args.key_id.as_ref(), args.private_key.as_ref()) {
+        (Some(key_id), Some(private_key)) => {
+            let pem = std::fs::read_to_string(private_key)?;
+            Some(smacktivity_network::Signer::from_pem(key_id.clone(), &pem)?)
+        }
+        _ => None,
+    };
+    let signer = signer.as_ref();
+
     let mut object = serde_json::from_reader::<_, smacktivity::Object>(std::io::stdin())?;
 
     use smacktivity_network::Resolved;
@@ -24,7 +39,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         match property.to_snake_case().as_str() {
             "actor" => {
                 for actor in object.actor.iter_mut() {
-                    let actor = actor.resolved().await?;
+                    let actor = actor.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", actor);
                     } else {
@@ -34,7 +49,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "attachment" => {
                 for attachment in object.attachment.iter_mut() {
-                    let attachment = attachment.resolved().await?;
+                    let attachment = attachment.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", attachment);
                     } else {
@@ -44,7 +59,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "attributed_to" => {
                 for attributed_to in object.attributed_to.iter_mut() {
-                    let attributed_to = attributed_to.resolved().await?;
+                    let attributed_to = attributed_to.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", attributed_to);
                     } else {
@@ -54,7 +69,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "audience" => {
                 for audience in object.audience.iter_mut() {
-                    let audience = audience.resolved().await?;
+                    let audience = audience.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", audience);
                     } else {
@@ -64,7 +79,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "bcc" => {
                 for bcc in object.bcc.iter_mut() {
-                    let bcc = bcc.resolved().await?;
+                    let bcc = bcc.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", bcc);
                     } else {
@@ -74,7 +89,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "bto" => {
                 for bto in object.bto.iter_mut() {
-                    let bto = bto.resolved().await?;
+                    let bto = bto.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", bto);
                     } else {
@@ -84,7 +99,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "cc" => {
                 for cc in object.cc.iter_mut() {
-                    let cc = cc.resolved().await?;
+                    let cc = cc.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", cc);
                     } else {
@@ -94,7 +109,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "context" => {
                 for context in object.context.iter_mut() {
-                    let context = context.resolved().await?;
+                    let context = context.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", context);
                     } else {
@@ -104,7 +119,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "current" => {
                 for current in object.current.iter_mut() {
-                    let current = current.resolved().await?;
+                    let current = current.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", current);
                     } else {
@@ -114,7 +129,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "first" => {
                 for first in object.first.iter_mut() {
-                    let first = first.resolved().await?;
+                    let first = first.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", first);
                     } else {
@@ -124,7 +139,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "generator" => {
                 for generator in object.generator.iter_mut() {
-                    let generator = generator.resolved().await?;
+                    let generator = generator.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", generator);
                     } else {
@@ -134,7 +149,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "icon" => {
                 for icon in object.icon.iter_mut() {
-                    let icon = icon.resolved().await?;
+                    let icon = icon.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", icon);
                     } else {
@@ -144,7 +159,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "image" => {
                 for image in object.image.iter_mut() {
-                    let image = image.resolved().await?;
+                    let image = image.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", image);
                     } else {
@@ -154,7 +169,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "in_reply_to" => {
                 for in_reply_to in object.in_reply_to.iter_mut() {
-                    let in_reply_to = in_reply_to.resolved().await?;
+                    let in_reply_to = in_reply_to.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", in_reply_to);
                     } else {
@@ -164,7 +179,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "instrument" => {
                 for instrument in object.instrument.iter_mut() {
-                    let instrument = instrument.resolved().await?;
+                    let instrument = instrument.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", instrument);
                     } else {
@@ -174,7 +189,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "last" => {
                 for last in object.last.iter_mut() {
-                    let last = last.resolved().await?;
+                    let last = last.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", last);
                     } else {
@@ -184,7 +199,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "location" => {
                 for location in object.location.iter_mut() {
-                    let location = location.resolved().await?;
+                    let location = location.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", location);
                     } else {
@@ -194,7 +209,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "items" => {
                 for items in object.items.iter_mut() {
-                    let items = items.resolved().await?;
+                    let items = items.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", items);
                     } else {
@@ -204,7 +219,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "ordered_items" => {
                 for ordered_items in object.ordered_items.iter_mut() {
-                    let ordered_items = ordered_items.resolved().await?;
+                    let ordered_items = ordered_items.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", ordered_items);
                     } else {
@@ -214,7 +229,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "one_of" => {
                 for one_of in object.one_of.iter_mut() {
-                    let one_of = one_of.resolved().await?;
+                    let one_of = one_of.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", one_of);
                     } else {
@@ -224,7 +239,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "any_of" => {
                 for any_of in object.any_of.iter_mut() {
-                    let any_of = any_of.resolved().await?;
+                    let any_of = any_of.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", any_of);
                     } else {
@@ -234,7 +249,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "origin" => {
                 for origin in object.origin.iter_mut() {
-                    let origin = origin.resolved().await?;
+                    let origin = origin.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", origin);
                     } else {
@@ -244,7 +259,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "next" => {
                 for next in object.next.iter_mut() {
-                    let next = next.resolved().await?;
+                    let next = next.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", next);
                     } else {
@@ -254,7 +269,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "object" => {
                 for object in object.object.iter_mut() {
-                    let object = object.resolved().await?;
+                    let object = object.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", object);
                     } else {
@@ -264,7 +279,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "prev" => {
                 for prev in object.prev.iter_mut() {
-                    let prev = prev.resolved().await?;
+                    let prev = prev.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", prev);
                     } else {
@@ -274,7 +289,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "preview" => {
                 for preview in object.preview.iter_mut() {
-                    let preview = preview.resolved().await?;
+                    let preview = preview.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", preview);
                     } else {
@@ -284,7 +299,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "result" => {
                 for result in object.result.iter_mut() {
-                    let result = result.resolved().await?;
+                    let result = result.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", result);
                     } else {
@@ -294,7 +309,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "replies" => {
                 for replies in object.replies.iter_mut() {
-                    let replies = replies.resolved().await?;
+                    let replies = replies.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", replies);
                     } else {
@@ -304,7 +319,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "tag" => {
                 for tag in object.tag.iter_mut() {
-                    let tag = tag.resolved().await?;
+                    let tag = tag.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", tag);
                     } else {
@@ -314,7 +329,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "target" => {
                 for target in object.target.iter_mut() {
-                    let target = target.resolved().await?;
+                    let target = target.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", target);
                     } else {
@@ -324,7 +339,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "to" => {
                 for to in object.to.iter_mut() {
-                    let to = to.resolved().await?;
+                    let to = to.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", to);
                     } else {
@@ -334,7 +349,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "url" => {
                 for url in object.url.iter_mut() {
-                    let url = url.resolved().await?;
+                    let url = url.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", url);
                     } else {
@@ -344,7 +359,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "part_of" => {
                 for part_of in object.part_of.iter_mut() {
-                    let part_of = part_of.resolved().await?;
+                    let part_of = part_of.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", part_of);
                     } else {
@@ -354,7 +369,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "subject" => {
                 for subject in object.subject.iter_mut() {
-                    let subject = subject.resolved().await?;
+                    let subject = subject.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", subject);
                     } else {
@@ -364,7 +379,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "relationship" => {
                 for relationship in object.relationship.iter_mut() {
-                    let relationship = relationship.resolved().await?;
+                    let relationship = relationship.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", relationship);
                     } else {
@@ -372,19 +387,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
             }
-            "former_type" => {
-                for former_type in object.former_type.iter_mut() {
-                    let former_type = former_type.resolved().await?;
-                    if args.debug {
-                        println!("{:#?}", former_type);
-                    } else {
-                        println!("{}", serde_json::to_string(&former_type)?);
-                    }
-                }
-            }
             "inbox" => {
                 for inbox in object.inbox.iter_mut() {
-                    let inbox = inbox.resolved().await?;
+                    let inbox = inbox.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", inbox);
                     } else {
@@ -394,7 +399,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "outbox" => {
                 for outbox in object.outbox.iter_mut() {
-                    let outbox = outbox.resolved().await?;
+                    let outbox = outbox.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", outbox);
                     } else {
@@ -404,7 +409,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "following" => {
                 for following in object.following.iter_mut() {
-                    let following = following.resolved().await?;
+                    let following = following.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", following);
                     } else {
@@ -414,7 +419,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "followers" => {
                 for followers in object.followers.iter_mut() {
-                    let followers = followers.resolved().await?;
+                    let followers = followers.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", followers);
                     } else {
@@ -424,7 +429,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "liked" => {
                 for liked in object.liked.iter_mut() {
-                    let liked = liked.resolved().await?;
+                    let liked = liked.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", liked);
                     } else {
@@ -434,7 +439,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             "streams" => {
                 for streams in object.streams.iter_mut() {
-                    let streams = streams.resolved().await?;
+                    let streams = streams.resolved(signer).await?;
                     if args.debug {
                         println!("{:#?}", streams);
                     } else {
@@ -443,6 +448,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
 
+            "former_type" => {
+                for former_type in object.former_type.iter() {
+                    if args.debug {
+                        println!("{:#?}", former_type);
+                    } else {
+                        println!("{}", serde_json::to_string(former_type)?);
+                    }
+                }
+            }
             "describes" => {
                 if let Some(describes) = object.describes.as_ref() {
                     if args.debug {
@@ -3,8 +3,8 @@ use url::Url;
 fn main() {
     let obj = smacktivity::Object {
         id: Some(Url::parse("https://grape.surgery/smack/outbox").unwrap()),
-        type_: smacktivity::ActivityStreamsType::Actor,
-        deleted: Some(String::from("eysterday lol")),
+        type_: smacktivity::ActivityStreamsType::Actor.into(),
+        deleted: Some(smacktivity::DateTime::now()),
         units: Some(smacktivity::Units::Km),
         rel: smacktivity::NonFunctional::One(smacktivity::LinkRelation::Alternate),
         ..Default::default()
@@ -0,0 +1,448 @@
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateTimeError {
+    input: String,
+    reason: &'static str,
+}
+
+impl std::fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid xsd:dateTime {:?}: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for DateTimeError {}
+
+/// An xsd:dateTime value.
+///
+/// Accepts RFC 3339 timestamps as well as the xsd:dateTime forms that RFC 3339
+/// doesn't allow, like a missing timezone, and keeps the original text around
+/// so that serializing gives back exactly what was parsed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct DateTime {
+    lexical: String,
+    value: Option<(NaiveDateTime, Option<FixedOffset>)>,
+}
+
+impl DateTime {
+    pub fn parse(input: &str) -> Result<DateTime, DateTimeError> {
+        let value = parse_lexical(input, false).map_err(|reason| DateTimeError {
+            input: String::from(input),
+            reason,
+        })?;
+
+        Ok(DateTime {
+            lexical: String::from(input),
+            value: Some(value),
+        })
+    }
+
+    /// Like [DateTime::parse], but accepts timezone offsets beyond ±14:00 and
+    /// keeps malformed values as raw strings instead of failing. They still
+    /// serialize back out unchanged.
+    pub fn parse_lenient(input: &str) -> DateTime {
+        match parse_lexical(input, true) {
+            Ok(value) => DateTime {
+                lexical: String::from(input),
+                value: Some(value),
+            },
+            Err(_) => DateTime::raw(input),
+        }
+    }
+
+    /// Keep `input` as-is without parsing it.
+    pub fn raw(input: impl Into<String>) -> DateTime {
+        DateTime {
+            lexical: input.into(),
+            value: None,
+        }
+    }
+
+    pub fn now() -> DateTime {
+        DateTime::from(Utc::now())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.lexical
+    }
+
+    /// Whether this value was kept raw by lenient parsing.
+    pub fn is_raw(&self) -> bool {
+        self.value.is_none()
+    }
+
+    pub fn naive(&self) -> Option<NaiveDateTime> {
+        self.value.map(|(naive, _)| naive)
+    }
+
+    /// The timezone offset, if the value had one.
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.value.and_then(|(_, offset)| offset)
+    }
+
+    /// The instant this value refers to. Values without a timezone are
+    /// assumed to be in UTC.
+    pub fn to_utc(&self) -> Option<chrono::DateTime<Utc>> {
+        let (naive, offset) = self.value?;
+        match offset {
+            Some(offset) => offset
+                .from_local_datetime(&naive)
+                .single()
+                .map(|datetime| datetime.with_timezone(&Utc)),
+            None => Some(Utc.from_utc_datetime(&naive)),
+        }
+    }
+}
+
+impl<Tz> From<chrono::DateTime<Tz>> for DateTime
+where
+    Tz: TimeZone,
+{
+    fn from(datetime: chrono::DateTime<Tz>) -> Self {
+        let datetime = datetime.fixed_offset();
+        let offset = datetime.offset().local_minus_utc();
+        DateTime {
+            lexical: datetime.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, offset == 0),
+            value: Some((datetime.naive_local(), Some(*datetime.offset()))),
+        }
+    }
+}
+
+impl std::str::FromStr for DateTime {
+    type Err = DateTimeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DateTime::parse(s)
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lexical)
+    }
+}
+
+impl std::fmt::Debug for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_raw() {
+            f.debug_tuple("DateTime::Raw").field(&self.lexical).finish()
+        } else {
+            f.debug_tuple("DateTime").field(&self.lexical).finish()
+        }
+    }
+}
+
+impl serde::Serialize for DateTime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.lexical)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for DateTime {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct DateTimeVisitor;
+        impl<'de> serde::de::Visitor<'de> for DateTimeVisitor {
+            type Value = DateTime;
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "xsd:dateTime string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                DateTime::parse(v).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(DateTimeVisitor)
+    }
+}
+
+/// For `#[serde(with = "smacktivity::datetime::lenient")]`, to deserialize a
+/// field with [DateTime::parse_lenient].
+pub mod lenient {
+    use super::DateTime;
+
+    pub fn serialize<S>(datetime: &DateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(datetime, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let input: std::borrow::Cow<'de, str> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(DateTime::parse_lenient(&input))
+    }
+}
+
+// -?YYYY-MM-DDThh:mm:ss(.s+)?(Z|(+|-)hh:mm)?
+fn parse_lexical(
+    input: &str,
+    lenient: bool,
+) -> Result<(NaiveDateTime, Option<FixedOffset>), &'static str> {
+    let mut cursor = Cursor(input.as_bytes());
+
+    let negative = cursor.eat(b'-');
+    let year_digits = cursor.digits();
+    if year_digits.len() < 4 {
+        return Err("year must have at least four digits");
+    }
+    if year_digits.len() > 4 && year_digits[0] == b'0' {
+        return Err("year has leading zeros");
+    }
+    let year = number(year_digits).ok_or("year out of range")?;
+    let year = if negative { -year } else { year };
+
+    cursor.expect(b'-', "expected '-' after year")?;
+    let month = cursor.two_digits("month must have two digits")?;
+    cursor.expect(b'-', "expected '-' after month")?;
+    let day = cursor.two_digits("day must have two digits")?;
+
+    if !(cursor.eat(b'T') || cursor.eat(b't')) {
+        return Err("expected 'T' between date and time");
+    }
+
+    let hour = cursor.two_digits("hour must have two digits")?;
+    cursor.expect(b':', "expected ':' after hour")?;
+    let minute = cursor.two_digits("minute must have two digits")?;
+    cursor.expect(b':', "expected ':' after minute")?;
+    let second = cursor.two_digits("second must have two digits")?;
+
+    let mut nanos = 0;
+    if cursor.eat(b'.') {
+        let fraction = cursor.digits();
+        if fraction.is_empty() {
+            return Err("expected digits after '.'");
+        }
+        for (i, digit) in fraction.iter().take(9).enumerate() {
+            nanos += (digit - b'0') as u32 * 10u32.pow(8 - i as u32);
+        }
+    }
+
+    let offset = if cursor.eat(b'Z') || cursor.eat(b'z') {
+        Some(FixedOffset::east_opt(0).unwrap())
+    } else if let Some(sign @ (b'+' | b'-')) = cursor.peek() {
+        cursor.advance();
+        let hours = cursor.two_digits("timezone hour must have two digits")?;
+        cursor.expect(b':', "expected ':' in timezone")?;
+        let minutes = cursor.two_digits("timezone minute must have two digits")?;
+        if hours > 23 || minutes > 59 {
+            return Err("timezone out of range");
+        }
+        // xsd:dateTime only allows offsets up to 14 hours
+        if (hours, minutes) > (14, 0) && !lenient {
+            return Err("timezone must be within ±14:00");
+        }
+        let seconds = (hours * 3600 + minutes * 60) as i32;
+        Some(
+            FixedOffset::east_opt(if sign == b'-' { -seconds } else { seconds })
+                .ok_or("timezone out of range")?,
+        )
+    } else {
+        None
+    };
+
+    if !cursor.0.is_empty() {
+        return Err("unexpected trailing characters");
+    }
+
+    let date = NaiveDate::from_ymd_opt(year, month, day).ok_or("no such date")?;
+    let datetime = match (hour, minute, second) {
+        // 24:00:00 is midnight at the end of the day
+        (24, 0, 0) if nanos == 0 => date
+            .succ_opt()
+            .ok_or("no such date")?
+            .and_time(NaiveTime::MIN),
+        // leap second
+        (_, _, 60) => date.and_time(
+            NaiveTime::from_hms_nano_opt(hour, minute, 59, 1_000_000_000 + nanos)
+                .ok_or("no such time")?,
+        ),
+        _ => date.and_time(
+            NaiveTime::from_hms_nano_opt(hour, minute, second, nanos).ok_or("no such time")?,
+        ),
+    };
+
+    Ok((datetime, offset))
+}
+
+fn number(digits: &[u8]) -> Option<i32> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+struct Cursor<'input>(&'input [u8]);
+
+impl<'input> Cursor<'input> {
+    fn peek(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    fn advance(&mut self) {
+        self.0 = &self.0[1..];
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8, reason: &'static str) -> Result<(), &'static str> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(reason)
+        }
+    }
+
+    fn digits(&mut self) -> &'input [u8] {
+        let len = self.0.iter().take_while(|b| b.is_ascii_digit()).count();
+        let (digits, rest) = self.0.split_at(len);
+        self.0 = rest;
+        digits
+    }
+
+    fn two_digits(&mut self, reason: &'static str) -> Result<u32, &'static str> {
+        match self.0 {
+            [a, b, ..] if a.is_ascii_digit() && b.is_ascii_digit() => {
+                self.0 = &self.0[2..];
+                Ok(((a - b'0') * 10 + (b - b'0')) as u32)
+            }
+            _ => Err(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(input: &str) -> String {
+        DateTime::parse(input)
+            .unwrap()
+            .to_utc()
+            .unwrap()
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn rfc3339() {
+        let datetime = DateTime::parse("2023-06-23T07:17:56Z").unwrap();
+        assert_eq!(datetime.offset(), FixedOffset::east_opt(0));
+        assert_eq!(utc("2023-06-23T07:17:56Z"), "2023-06-23T07:17:56+00:00");
+        assert_eq!(
+            utc("2023-06-23T09:17:56.25+02:00"),
+            "2023-06-23T07:17:56.250+00:00"
+        );
+    }
+
+    #[test]
+    fn xsd_forms() {
+        // no timezone, assumed to be UTC
+        let datetime = DateTime::parse("2023-06-23T07:17:56").unwrap();
+        assert_eq!(datetime.offset(), None);
+        assert_eq!(utc("2023-06-23T07:17:56"), "2023-06-23T07:17:56+00:00");
+
+        assert_eq!(utc("2023-06-23T24:00:00Z"), "2023-06-24T00:00:00+00:00");
+        assert!(DateTime::parse("12023-06-23T07:17:56Z").is_ok());
+        assert!(DateTime::parse("-0044-03-15T12:00:00Z").is_ok());
+        assert!(DateTime::parse("2016-12-31T23:59:60Z").is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        for input in [
+            "",
+            "2023-06-23",
+            "23-06-23T07:17:56Z",
+            "02023-06-23T07:17:56Z",
+            "2023-02-30T07:17:56Z",
+            "2023-06-23T25:00:00Z",
+            "2023-06-23T24:00:01Z",
+            "2023-06-23T07:17:56.Z",
+            "2023-06-23T07:17:56+0200",
+            "2023-06-23T07:17:56Z trailing",
+        ] {
+            assert!(DateTime::parse(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn timezone_limits() {
+        assert!(DateTime::parse("2023-06-23T07:17:56+14:00").is_ok());
+        assert!(DateTime::parse("2023-06-23T07:17:56-14:00").is_ok());
+        assert!(DateTime::parse("2023-06-23T07:17:56+14:01").is_err());
+        assert!(DateTime::parse("2023-06-23T07:17:56-23:59").is_err());
+        assert!(DateTime::parse("2023-06-23T07:17:56+24:00").is_err());
+
+        let lenient = DateTime::parse_lenient("2023-06-23T07:17:56-23:59");
+        assert!(!lenient.is_raw());
+        assert_eq!(
+            lenient.to_utc().unwrap().to_rfc3339(),
+            "2023-06-24T07:16:56+00:00"
+        );
+    }
+
+    #[test]
+    fn lenient_keeps_raw_values() {
+        let json = r#""yesterday lol""#;
+        assert!(serde_json::from_str::<DateTime>(json).is_err());
+
+        let datetime = DateTime::parse_lenient("yesterday lol");
+        assert!(datetime.is_raw());
+        assert_eq!(datetime.to_utc(), None);
+        assert_eq!(serde_json::to_string(&datetime).unwrap(), json);
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Event {
+            #[serde(with = "lenient")]
+            at: DateTime,
+        }
+        let json = r#"{"at":"yesterday lol"}"#;
+        let event: Event = serde_json::from_str(json).unwrap();
+        assert!(event.at.is_raw());
+        assert_eq!(serde_json::to_string(&event).unwrap(), json);
+        let event: Event = serde_json::from_str(r#"{"at":"2023-06-23T07:17:56Z"}"#).unwrap();
+        assert!(!event.at.is_raw());
+    }
+
+    #[test]
+    fn keeps_the_lexical_form() {
+        for input in ["2023-06-23T07:17:56z", "2023-06-23T07:17:56.000+00:00"] {
+            let datetime = DateTime::parse(input).unwrap();
+            assert_eq!(datetime.as_str(), input);
+            assert_eq!(serde_json::to_value(&datetime).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn published_dates() {
+        let outbox: crate::Object =
+            serde_json::from_str(include_str!("../../eg/darius.first.json")).unwrap();
+        let activity = outbox
+            .ordered_items
+            .iter()
+            .next()
+            .unwrap()
+            .as_object()
+            .unwrap();
+        assert_eq!(
+            activity.published.as_ref().map(DateTime::as_str),
+            Some("2023-06-23T22:45:49Z")
+        );
+    }
+}
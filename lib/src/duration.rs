@@ -0,0 +1,354 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationError {
+    input: String,
+    reason: &'static str,
+}
+
+impl std::fmt::Display for DurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid xsd:duration {:?}: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for DurationError {}
+
+/// An xsd:duration value, like `PT2M30S` or `P1Y2M`.
+///
+/// Keeps the original text so serializing gives back exactly what was parsed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Duration {
+    lexical: String,
+    negative: bool,
+    years: u64,
+    months: u64,
+    days: u64,
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+    nanos: u32,
+}
+
+impl Duration {
+    pub fn parse(input: &str) -> Result<Duration, DurationError> {
+        let error = |reason| DurationError {
+            input: String::from(input),
+            reason,
+        };
+
+        let mut duration = Duration {
+            lexical: String::from(input),
+            negative: false,
+            years: 0,
+            months: 0,
+            days: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+            nanos: 0,
+        };
+
+        let mut rest = input;
+        if let Some(after) = rest.strip_prefix('-') {
+            duration.negative = true;
+            rest = after;
+        }
+        rest = rest
+            .strip_prefix('P')
+            .ok_or_else(|| error("expected 'P'"))?;
+
+        let (date, time) = match rest.split_once('T') {
+            Some((_, "")) => return Err(error("expected time components after 'T'")),
+            Some((date, time)) => (date, Some(time)),
+            None => (rest, None),
+        };
+
+        if date.is_empty() && time.is_none() {
+            return Err(error("expected at least one component"));
+        }
+
+        let mut order = 0;
+        for (value, designator) in components(date).map_err(error)? {
+            let (position, field) = match designator {
+                'Y' => (1, &mut duration.years),
+                'M' => (2, &mut duration.months),
+                'D' => (3, &mut duration.days),
+                _ => return Err(error("unknown date component")),
+            };
+            if position <= order {
+                return Err(error("date components out of order"));
+            }
+            order = position;
+            *field = integer(value).map_err(error)?;
+        }
+
+        order = 0;
+        for (value, designator) in components(time.unwrap_or("")).map_err(error)? {
+            let position = match designator {
+                'H' => 1,
+                'M' => 2,
+                'S' => 3,
+                _ => return Err(error("unknown time component")),
+            };
+            if position <= order {
+                return Err(error("time components out of order"));
+            }
+            order = position;
+
+            match designator {
+                'H' => duration.hours = integer(value).map_err(error)?,
+                'M' => duration.minutes = integer(value).map_err(error)?,
+                _ => {
+                    let (whole, fraction) = match value.split_once('.') {
+                        Some((_, "")) => return Err(error("expected digits after '.'")),
+                        Some((whole, fraction)) => (whole, fraction),
+                        None => (value, ""),
+                    };
+                    duration.seconds = integer(whole).map_err(error)?;
+                    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                        return Err(error("seconds must be a decimal number"));
+                    }
+                    for (i, digit) in fraction.bytes().take(9).enumerate() {
+                        duration.nanos += (digit - b'0') as u32 * 10u32.pow(8 - i as u32);
+                    }
+                }
+            }
+        }
+
+        Ok(duration)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.lexical
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn years(&self) -> u64 {
+        self.years
+    }
+
+    pub fn months(&self) -> u64 {
+        self.months
+    }
+
+    pub fn days(&self) -> u64 {
+        self.days
+    }
+
+    pub fn hours(&self) -> u64 {
+        self.hours
+    }
+
+    pub fn minutes(&self) -> u64 {
+        self.minutes
+    }
+
+    /// Whole seconds, without the fractional part.
+    pub fn seconds(&self) -> u64 {
+        self.seconds
+    }
+
+    /// Fractional part of the seconds, in nanoseconds.
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Convert to a [std::time::Duration].
+    ///
+    /// Returns None if the duration is negative, if it has year or month
+    /// components (which don't have a fixed length), or if it overflows. Days
+    /// are taken to be 24 hours long.
+    pub fn to_std(&self) -> Option<std::time::Duration> {
+        if self.negative || self.years != 0 || self.months != 0 {
+            return None;
+        }
+
+        let seconds = self
+            .days
+            .checked_mul(24 * 60 * 60)?
+            .checked_add(self.hours.checked_mul(60 * 60)?)?
+            .checked_add(self.minutes.checked_mul(60)?)?
+            .checked_add(self.seconds)?;
+
+        Some(std::time::Duration::new(seconds, self.nanos))
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(std: std::time::Duration) -> Self {
+        let total = std.as_secs();
+        let mut duration = Duration {
+            lexical: String::new(),
+            negative: false,
+            years: 0,
+            months: 0,
+            days: 0,
+            hours: total / (60 * 60),
+            minutes: total / 60 % 60,
+            seconds: total % 60,
+            nanos: std.subsec_nanos(),
+        };
+
+        let mut lexical = String::from("PT");
+        if duration.hours != 0 {
+            lexical.push_str(&format!("{}H", duration.hours));
+        }
+        if duration.minutes != 0 {
+            lexical.push_str(&format!("{}M", duration.minutes));
+        }
+        if duration.nanos != 0 {
+            let fraction = format!("{:09}", duration.nanos);
+            lexical.push_str(&format!(
+                "{}.{}S",
+                duration.seconds,
+                fraction.trim_end_matches('0')
+            ));
+        } else if duration.seconds != 0 || lexical == "PT" {
+            lexical.push_str(&format!("{}S", duration.seconds));
+        }
+
+        duration.lexical = lexical;
+        duration
+    }
+}
+
+fn components(input: &str) -> Result<Vec<(&str, char)>, &'static str> {
+    let mut components = Vec::new();
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        if c.is_ascii_uppercase() {
+            if i == start {
+                return Err("expected a number before each designator");
+            }
+            components.push((&input[start..i], c));
+            start = i + 1;
+        }
+    }
+    if start != input.len() {
+        return Err("expected a designator after each number");
+    }
+    Ok(components)
+}
+
+fn integer(digits: &str) -> Result<u64, &'static str> {
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("components must be whole numbers");
+    }
+    digits.parse().map_err(|_| "component out of range")
+}
+
+impl std::str::FromStr for Duration {
+    type Err = DurationError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Duration::parse(s)
+    }
+}
+
+impl std::fmt::Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lexical)
+    }
+}
+
+impl std::fmt::Debug for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Duration").field(&self.lexical).finish()
+    }
+}
+
+impl serde::Serialize for Duration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.lexical)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Duration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct DurationVisitor;
+        impl<'de> serde::de::Visitor<'de> for DurationVisitor {
+            type Value = Duration;
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "xsd:duration string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Duration::parse(v).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(DurationVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components() {
+        let duration = Duration::parse("P1Y2M3DT4H5M6.75S").unwrap();
+        assert_eq!(
+            (
+                duration.years(),
+                duration.months(),
+                duration.days(),
+                duration.hours(),
+                duration.minutes(),
+                duration.seconds(),
+                duration.subsec_nanos(),
+            ),
+            (1, 2, 3, 4, 5, 6, 750_000_000)
+        );
+        assert!(!duration.is_negative());
+        assert!(Duration::parse("-P1D").unwrap().is_negative());
+    }
+
+    #[test]
+    fn to_std() {
+        let duration = Duration::parse("P1DT2M30.5S").unwrap();
+        assert_eq!(
+            duration.to_std(),
+            Some(std::time::Duration::new(24 * 60 * 60 + 150, 500_000_000))
+        );
+        assert_eq!(Duration::parse("P1M").unwrap().to_std(), None);
+        assert_eq!(Duration::parse("-PT1S").unwrap().to_std(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        for input in [
+            "", "P", "PT", "1D", "P1DT", "PT1D", "P1H", "P1M1Y", "PT1S1M", "P1.5D", "PT1.S",
+            "P-1D", "PD", "P1", "pt1s",
+        ] {
+            assert!(Duration::parse(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn from_std() {
+        let duration = Duration::from(std::time::Duration::new(3 * 60 * 60 + 61, 250_000_000));
+        assert_eq!(duration.as_str(), "PT3H1M1.25S");
+        assert_eq!(Duration::parse(duration.as_str()).unwrap(), duration);
+
+        assert_eq!(Duration::from(std::time::Duration::ZERO).as_str(), "PT0S");
+    }
+
+    #[test]
+    fn keeps_the_lexical_form() {
+        let duration: Duration = serde_json::from_str(r#""PT0120S""#).unwrap();
+        assert_eq!(duration.minutes(), 0);
+        assert_eq!(duration.seconds(), 120);
+        assert_eq!(serde_json::to_string(&duration).unwrap(), r#""PT0120S""#);
+    }
+}
@@ -0,0 +1,1473 @@
+//! JSON-LD expansion and compaction.
+//!
+//! This covers the parts of JSON-LD 1.1 that show up in ActivityPub documents:
+//! remote contexts (only the bundled ones, nothing is fetched), term
+//! definitions with type coercion and `@list`/`@language` containers, keyword
+//! aliases, `@vocab`, `@language`, property-scoped and embedded contexts, and
+//! protected terms. It is not a conformant processor, and compaction prefers
+//! plain values over value objects so the result deserializes cleanly into an
+//! [Object](crate::Object).
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use url::Url;
+
+pub mod validate;
+
+pub use validate::{validate_context, validate_document, Diagnostic, Diagnostics, Severity};
+
+pub const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+
+const KEYWORDS: &[&str] = &[
+    "@base",
+    "@container",
+    "@context",
+    "@direction",
+    "@graph",
+    "@id",
+    "@import",
+    "@included",
+    "@index",
+    "@json",
+    "@language",
+    "@list",
+    "@nest",
+    "@none",
+    "@prefix",
+    "@propagate",
+    "@protected",
+    "@reverse",
+    "@set",
+    "@type",
+    "@value",
+    "@version",
+    "@vocab",
+];
+
+#[derive(Debug)]
+pub enum JsonLdError {
+    UnknownRemoteContext(String),
+    RecursiveContextInclusion(String),
+    InvalidContext(String),
+    InvalidTermDefinition(String),
+    CyclicIriMapping(String),
+    KeywordRedefinition(String),
+    ProtectedTermRedefinition(String),
+    InvalidValue(String),
+    Deserialize(serde_json::Error),
+}
+
+impl std::fmt::Display for JsonLdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonLdError::UnknownRemoteContext(url) => {
+                write!(f, "no bundled copy of remote context {}", url)
+            }
+            JsonLdError::RecursiveContextInclusion(url) => {
+                write!(f, "context {} includes itself", url)
+            }
+            JsonLdError::InvalidContext(why) => write!(f, "invalid context: {}", why),
+            JsonLdError::InvalidTermDefinition(term) => {
+                write!(f, "invalid term definition for {:?}", term)
+            }
+            JsonLdError::CyclicIriMapping(term) => {
+                write!(f, "term {:?} is defined in terms of itself", term)
+            }
+            JsonLdError::KeywordRedefinition(keyword) => {
+                write!(f, "cannot redefine keyword {}", keyword)
+            }
+            JsonLdError::ProtectedTermRedefinition(term) => {
+                write!(f, "cannot redefine protected term {:?}", term)
+            }
+            JsonLdError::InvalidValue(why) => write!(f, "invalid value: {}", why),
+            JsonLdError::Deserialize(err) => write!(f, "couldn't deserialize object: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for JsonLdError {}
+
+/// Our offline copy of a remote context document, if we have one.
+pub fn bundled_context(url: &str) -> Option<&'static Value> {
+    static ACTIVITYSTREAMS: OnceLock<Value> = OnceLock::new();
+    static SECURITY: OnceLock<Value> = OnceLock::new();
+
+    let url = url.trim_end_matches('#');
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+
+    let (cell, source) = match url.trim_end_matches(".jsonld").trim_end_matches('/') {
+        "www.w3.org/ns/activitystreams" => (
+            &ACTIVITYSTREAMS,
+            include_str!("jsonld/activitystreams.jsonld"),
+        ),
+        "w3id.org/security/v1" => (&SECURITY, include_str!("jsonld/security-v1.jsonld")),
+        _ => return None,
+    };
+
+    Some(cell.get_or_init(|| serde_json::from_str(source).expect("bundled context is valid JSON")))
+}
+
+/// The terms Mastodon defines inline in the `@context` of everything it
+/// serves, as in `eg/darius.first.json`. They're from `CONTEXT_EXTENSION_MAP`
+/// in Mastodon's `app/lib/activitypub/adapter.rb`; there's no remote context
+/// document for them.
+pub fn mastodon_terms() -> &'static Value {
+    static TERMS: OnceLock<Value> = OnceLock::new();
+    TERMS.get_or_init(|| {
+        serde_json::from_str(include_str!("jsonld/mastodon-terms.json"))
+            .expect("bundled terms are valid JSON")
+    })
+}
+
+/// The context we compact documents against: ActivityStreams, the security
+/// vocabulary, and [mastodon_terms].
+pub fn default_context() -> Value {
+    Value::Array(vec![
+        Value::String(String::from(crate::ACTIVITYSTREAMS_CONTEXT)),
+        Value::String(String::from(SECURITY_CONTEXT)),
+        mastodon_terms().clone(),
+    ])
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermDefinition {
+    /// None if the term was explicitly mapped to null.
+    pub iri: Option<String>,
+    pub type_mapping: Option<String>,
+    pub container: Vec<String>,
+    /// Some(None) if the term explicitly has no language.
+    pub language: Option<Option<String>>,
+    pub reverse: bool,
+    pub protected: bool,
+    pub prefix: bool,
+    pub context: Option<Value>,
+}
+
+impl TermDefinition {
+    fn has_container(&self, container: &str) -> bool {
+        self.container.iter().any(|c| c == container)
+    }
+
+    // everything except the protected flag
+    fn same_definition(&self, other: &TermDefinition) -> bool {
+        self.iri == other.iri
+            && self.type_mapping == other.type_mapping
+            && self.container == other.container
+            && self.language == other.language
+            && self.reverse == other.reverse
+            && self.prefix == other.prefix
+            && self.context == other.context
+    }
+}
+
+/// An active context.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    terms: HashMap<String, TermDefinition>,
+    vocab: Option<String>,
+    language: Option<String>,
+    base: Option<Url>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    /// Process the value of an `@context` entry on top of an empty context.
+    pub fn parse(context: &Value) -> Result<Context, JsonLdError> {
+        Context::new().process(context)
+    }
+
+    /// Process the value of an `@context` entry on top of this context.
+    pub fn process(&self, local: &Value) -> Result<Context, JsonLdError> {
+        self.process_inner(local, &mut Vec::new(), false)
+    }
+
+    pub fn term(&self, term: &str) -> Option<&TermDefinition> {
+        self.terms.get(term)
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = (&str, &TermDefinition)> {
+        self.terms
+            .iter()
+            .map(|(term, definition)| (term.as_str(), definition))
+    }
+
+    pub fn vocab(&self) -> Option<&str> {
+        self.vocab.as_deref()
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    fn process_inner(
+        &self,
+        local: &Value,
+        remote: &mut Vec<String>,
+        override_protected: bool,
+    ) -> Result<Context, JsonLdError> {
+        let mut result = self.clone();
+
+        let contexts = match local {
+            Value::Array(contexts) => contexts.as_slice(),
+            other => std::slice::from_ref(other),
+        };
+
+        for context in contexts {
+            match context {
+                Value::Null => {
+                    if !override_protected {
+                        if let Some((term, _)) = result.terms().find(|(_, def)| def.protected) {
+                            return Err(JsonLdError::ProtectedTermRedefinition(String::from(term)));
+                        }
+                    }
+                    result = Context {
+                        base: result.base.take(),
+                        ..Context::default()
+                    };
+                }
+
+                Value::String(url) => {
+                    let url = match result.base.as_ref().map(|base| base.join(url)) {
+                        Some(Ok(joined)) => joined.to_string(),
+                        _ => url.clone(),
+                    };
+                    if remote.contains(&url) {
+                        return Err(JsonLdError::RecursiveContextInclusion(url));
+                    }
+
+                    let document = bundled_context(&url)
+                        .ok_or_else(|| JsonLdError::UnknownRemoteContext(url.clone()))?;
+                    let context = document.get("@context").ok_or_else(|| {
+                        JsonLdError::InvalidContext(format!("{} has no @context", url))
+                    })?;
+
+                    remote.push(url);
+                    result = result.process_inner(context, remote, override_protected)?;
+                    remote.pop();
+                }
+
+                Value::Object(map) => {
+                    if let Some(base) = map.get("@base") {
+                        result.base = match base {
+                            Value::Null => None,
+                            Value::String(base) => Some(Url::parse(base).map_err(|_| {
+                                JsonLdError::InvalidContext(format!("invalid @base {:?}", base))
+                            })?),
+                            _ => {
+                                return Err(JsonLdError::InvalidContext(String::from(
+                                    "@base must be a string",
+                                )))
+                            }
+                        };
+                    }
+
+                    if let Some(vocab) = map.get("@vocab") {
+                        result.vocab = match vocab {
+                            Value::Null => None,
+                            Value::String(vocab) => result.expand_iri(vocab, true, true),
+                            _ => {
+                                return Err(JsonLdError::InvalidContext(String::from(
+                                    "@vocab must be a string",
+                                )))
+                            }
+                        };
+                    }
+
+                    if let Some(language) = map.get("@language") {
+                        result.language = match language {
+                            Value::Null => None,
+                            Value::String(language) => Some(language.clone()),
+                            _ => {
+                                return Err(JsonLdError::InvalidContext(String::from(
+                                    "@language must be a string",
+                                )))
+                            }
+                        };
+                    }
+
+                    let protected = match map.get("@protected") {
+                        None => false,
+                        Some(Value::Bool(protected)) => *protected,
+                        _ => {
+                            return Err(JsonLdError::InvalidContext(String::from(
+                                "@protected must be a boolean",
+                            )))
+                        }
+                    };
+
+                    let mut creator = TermCreator {
+                        local: map,
+                        defined: HashMap::new(),
+                        protected,
+                        override_protected,
+                    };
+                    for term in map.keys() {
+                        if !matches!(
+                            term.as_str(),
+                            "@base"
+                                | "@direction"
+                                | "@import"
+                                | "@language"
+                                | "@propagate"
+                                | "@protected"
+                                | "@version"
+                                | "@vocab"
+                        ) {
+                            creator.create(&mut result, term)?;
+                        }
+                    }
+                }
+
+                _ => {
+                    return Err(JsonLdError::InvalidContext(String::from(
+                        "context must be null, a string, or an object",
+                    )))
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Expand a term, compact IRI, or relative IRI to an absolute IRI or
+    /// keyword. Returns None if the value expands to nothing, like a term
+    /// mapped to null.
+    pub fn expand_iri(&self, value: &str, vocab: bool, document_relative: bool) -> Option<String> {
+        if is_keyword(value) {
+            return Some(String::from(value));
+        }
+        if looks_like_keyword(value) {
+            return None;
+        }
+
+        if vocab {
+            if let Some(definition) = self.terms.get(value) {
+                return definition.iri.clone();
+            }
+        }
+
+        if let Some((prefix, suffix)) = value.split_once(':') {
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(String::from(value));
+            }
+            if let Some(TermDefinition { iri: Some(iri), .. }) = self.terms.get(prefix) {
+                return Some(format!("{}{}", iri, suffix));
+            }
+            if is_absolute_iri(value) {
+                return Some(String::from(value));
+            }
+        }
+
+        if vocab {
+            if let Some(vocab) = self.vocab.as_ref() {
+                return Some(format!("{}{}", vocab, value));
+            }
+        }
+
+        if document_relative {
+            if let Some(Ok(joined)) = self.base.as_ref().map(|base| base.join(value)) {
+                return Some(joined.to_string());
+            }
+        }
+
+        Some(String::from(value))
+    }
+}
+
+struct TermCreator<'local> {
+    local: &'local Map<String, Value>,
+    defined: HashMap<String, bool>,
+    protected: bool,
+    override_protected: bool,
+}
+
+impl TermCreator<'_> {
+    fn create(&mut self, context: &mut Context, term: &str) -> Result<(), JsonLdError> {
+        match self.defined.get(term) {
+            Some(true) => return Ok(()),
+            Some(false) => return Err(JsonLdError::CyclicIriMapping(String::from(term))),
+            None => {}
+        }
+
+        if term.is_empty() {
+            return Err(JsonLdError::InvalidTermDefinition(String::from(term)));
+        }
+
+        let value = match self.local.get(term) {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+
+        if is_keyword(term) {
+            return Err(JsonLdError::KeywordRedefinition(String::from(term)));
+        }
+        if looks_like_keyword(term) {
+            // reserved for future keywords, ignored
+            return Ok(());
+        }
+
+        self.defined.insert(String::from(term), false);
+
+        let previous = context.terms.remove(term);
+
+        let simple = value.is_string();
+        let map = match value {
+            Value::Null => {
+                let mut map = Map::new();
+                map.insert(String::from("@id"), Value::Null);
+                map
+            }
+            Value::String(iri) => {
+                let mut map = Map::new();
+                map.insert(String::from("@id"), Value::String(iri.clone()));
+                map
+            }
+            Value::Object(map) => map.clone(),
+            _ => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        };
+
+        let mut definition = TermDefinition {
+            iri: None,
+            type_mapping: None,
+            container: Vec::new(),
+            language: None,
+            reverse: false,
+            protected: self.protected,
+            prefix: false,
+            context: None,
+        };
+
+        match map.get("@protected") {
+            None => {}
+            Some(Value::Bool(protected)) => definition.protected = *protected,
+            Some(_) => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        }
+
+        match map.get("@type") {
+            None => {}
+            Some(Value::String(type_)) => {
+                let expanded = if matches!(type_.as_str(), "@id" | "@vocab" | "@json" | "@none") {
+                    type_.clone()
+                } else {
+                    self.expand_iri(context, type_)?
+                        .filter(|iri| is_absolute_iri(iri) || iri.starts_with("_:"))
+                        .ok_or_else(|| JsonLdError::InvalidTermDefinition(String::from(term)))?
+                };
+                definition.type_mapping = Some(expanded);
+            }
+            Some(_) => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        }
+
+        if let Some(reverse) = map.get("@reverse") {
+            let reverse = reverse
+                .as_str()
+                .ok_or_else(|| JsonLdError::InvalidTermDefinition(String::from(term)))?;
+            definition.iri = self.expand_iri(context, reverse)?;
+            definition.reverse = true;
+        } else if let Some(id) = map.get("@id").filter(|id| id.as_str() != Some(term)) {
+            match id {
+                Value::Null => definition.iri = None,
+                Value::String(id) => {
+                    let iri = self
+                        .expand_iri(context, id)?
+                        .ok_or_else(|| JsonLdError::InvalidTermDefinition(String::from(term)))?;
+                    if !is_keyword(&iri) && !iri.contains(':') {
+                        return Err(JsonLdError::InvalidTermDefinition(String::from(term)));
+                    }
+                    if simple
+                        && !term.contains(':')
+                        && !term.contains('/')
+                        && (iri.starts_with("_:") || iri.ends_with(is_gen_delim))
+                    {
+                        definition.prefix = true;
+                    }
+                    definition.iri = Some(iri);
+                }
+                _ => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+            }
+        } else if let Some((prefix, suffix)) = term.split_once(':').filter(|(p, _)| !p.is_empty()) {
+            if self.local.contains_key(prefix) {
+                self.create(context, prefix)?;
+            }
+            definition.iri = match context.terms.get(prefix) {
+                Some(TermDefinition { iri: Some(iri), .. }) => Some(format!("{}{}", iri, suffix)),
+                _ => Some(String::from(term)),
+            };
+        } else if term.contains('/') {
+            definition.iri = context.expand_iri(term, false, true);
+        } else if let Some(vocab) = context.vocab.as_ref() {
+            definition.iri = Some(format!("{}{}", vocab, term));
+        } else {
+            return Err(JsonLdError::InvalidTermDefinition(String::from(term)));
+        }
+
+        match map.get("@container") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(container)) => definition.container.push(container.clone()),
+            Some(Value::Array(containers)) => {
+                for container in containers {
+                    let container = container
+                        .as_str()
+                        .ok_or_else(|| JsonLdError::InvalidTermDefinition(String::from(term)))?;
+                    definition.container.push(String::from(container));
+                }
+            }
+            Some(_) => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        }
+        if definition.container.iter().any(|container| {
+            !matches!(
+                container.as_str(),
+                "@list" | "@set" | "@language" | "@index" | "@id" | "@type" | "@graph"
+            )
+        }) {
+            return Err(JsonLdError::InvalidTermDefinition(String::from(term)));
+        }
+
+        match map.get("@language") {
+            None => {}
+            Some(Value::Null) => definition.language = Some(None),
+            Some(Value::String(language)) => definition.language = Some(Some(language.clone())),
+            Some(_) => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        }
+
+        match map.get("@prefix") {
+            None => {}
+            Some(Value::Bool(prefix)) => definition.prefix = *prefix,
+            Some(_) => return Err(JsonLdError::InvalidTermDefinition(String::from(term))),
+        }
+
+        definition.context = map.get("@context").cloned();
+
+        if let Some(previous) = previous {
+            if previous.protected && !self.override_protected {
+                if !previous.same_definition(&definition) {
+                    return Err(JsonLdError::ProtectedTermRedefinition(String::from(term)));
+                }
+                definition = previous;
+            }
+        }
+
+        context.terms.insert(String::from(term), definition);
+        self.defined.insert(String::from(term), true);
+
+        Ok(())
+    }
+
+    fn expand_iri(
+        &mut self,
+        context: &mut Context,
+        value: &str,
+    ) -> Result<Option<String>, JsonLdError> {
+        if self.local.contains_key(value) && !is_keyword(value) {
+            self.create(context, value)?;
+        }
+        if let Some((prefix, _)) = value.split_once(':') {
+            if self.local.contains_key(prefix) {
+                self.create(context, prefix)?;
+            }
+        }
+        Ok(context.expand_iri(value, true, true))
+    }
+}
+
+/// Expand a document against its own `@context`.
+///
+/// The result is always an array of node objects whose properties are full
+/// IRIs.
+pub fn expand(document: &Value) -> Result<Value, JsonLdError> {
+    let expanded = expand_element(&Context::new(), None, document)?;
+
+    let expanded = match expanded {
+        Value::Object(mut map) if map.len() == 1 && map.contains_key("@graph") => {
+            map.remove("@graph").unwrap()
+        }
+        Value::Null => Value::Array(Vec::new()),
+        other => other,
+    };
+
+    Ok(match expanded {
+        Value::Array(array) => Value::Array(array),
+        other => Value::Array(vec![other]),
+    })
+}
+
+fn expand_element(
+    context: &Context,
+    property: Option<&str>,
+    element: &Value,
+) -> Result<Value, JsonLdError> {
+    let definition = property.and_then(|property| context.term(property));
+
+    match element {
+        Value::Null => Ok(Value::Null),
+
+        Value::Array(items) => {
+            let mut result = Vec::new();
+            for item in items {
+                match expand_element(context, property, item)? {
+                    Value::Null => {}
+                    Value::Array(nested) => result.extend(nested),
+                    expanded => result.push(expanded),
+                }
+            }
+            Ok(Value::Array(result))
+        }
+
+        Value::Object(map) => {
+            let mut context = context.clone();
+            if let Some(scoped) = definition.and_then(|definition| definition.context.as_ref()) {
+                context = context.process_inner(scoped, &mut Vec::new(), true)?;
+            }
+            if let Some(embedded) = map.get("@context") {
+                context = context.process(embedded)?;
+            }
+
+            let mut result = Map::new();
+            for (key, value) in map {
+                if key == "@context" {
+                    continue;
+                }
+
+                let expanded_property = match context.expand_iri(key, true, false) {
+                    Some(expanded) if expanded.contains(':') || is_keyword(&expanded) => expanded,
+                    _ => continue,
+                };
+
+                if is_keyword(&expanded_property) {
+                    let expanded_value = match expanded_property.as_str() {
+                        "@id" => match value {
+                            Value::String(id) => Value::String(
+                                context
+                                    .expand_iri(id, false, true)
+                                    .unwrap_or_else(|| id.clone()),
+                            ),
+                            _ => return Err(JsonLdError::InvalidValue(String::from("@id"))),
+                        },
+                        "@type" => {
+                            let types = match value {
+                                Value::String(_) => std::slice::from_ref(value),
+                                Value::Array(types) => types.as_slice(),
+                                _ => return Err(JsonLdError::InvalidValue(String::from("@type"))),
+                            };
+                            let mut expanded = Vec::new();
+                            for type_ in types {
+                                let type_ = type_.as_str().ok_or_else(|| {
+                                    JsonLdError::InvalidValue(String::from("@type"))
+                                })?;
+                                if let Some(iri) = context.expand_iri(type_, true, true) {
+                                    expanded.push(Value::String(iri));
+                                }
+                            }
+                            Value::Array(expanded)
+                        }
+                        "@value" | "@language" | "@index" | "@direction" => value.clone(),
+                        "@list" | "@set" | "@graph" | "@included" => {
+                            match expand_element(&context, property, value)? {
+                                Value::Array(array) => Value::Array(array),
+                                Value::Null => Value::Array(Vec::new()),
+                                other => Value::Array(vec![other]),
+                            }
+                        }
+                        _ => continue,
+                    };
+
+                    merge_value(&mut result, &expanded_property, expanded_value);
+                    continue;
+                }
+
+                let term = context.term(key);
+                let container = |container: &str| term.is_some_and(|t| t.has_container(container));
+
+                let expanded_value =
+                    if term.and_then(|t| t.type_mapping.as_deref()) == Some("@json") {
+                        let mut object = Map::new();
+                        object.insert(String::from("@value"), value.clone());
+                        object.insert(String::from("@type"), Value::String(String::from("@json")));
+                        Value::Object(object)
+                    } else if container("@language") && value.is_object() {
+                        let mut values = Vec::new();
+                        for (language, strings) in value.as_object().unwrap() {
+                            let strings = match strings {
+                                Value::Array(strings) => strings.as_slice(),
+                                other => std::slice::from_ref(other),
+                            };
+                            for string in strings {
+                                match string {
+                                    Value::Null => {}
+                                    Value::String(_) => {
+                                        let mut object = Map::new();
+                                        object.insert(String::from("@value"), string.clone());
+                                        if language != "@none"
+                                            && context.expand_iri(language, true, false).as_deref()
+                                                != Some("@none")
+                                        {
+                                            object.insert(
+                                                String::from("@language"),
+                                                Value::String(language.clone()),
+                                            );
+                                        }
+                                        values.push(Value::Object(object));
+                                    }
+                                    _ => {
+                                        return Err(JsonLdError::InvalidValue(format!(
+                                            "language map {} must contain strings",
+                                            key
+                                        )))
+                                    }
+                                }
+                            }
+                        }
+                        Value::Array(values)
+                    } else if value.is_object() || value.is_array() {
+                        expand_element(&context, Some(key), value)?
+                    } else if value.is_null() {
+                        Value::Null
+                    } else {
+                        expand_value(&context, key, value)
+                    };
+
+                let expanded_value = match expanded_value {
+                    Value::Null => continue,
+                    Value::Array(array) => array,
+                    other => vec![other],
+                };
+
+                let expanded_value = if container("@list")
+                    && !(expanded_value.len() == 1 && expanded_value[0].get("@list").is_some())
+                {
+                    let mut list = Map::new();
+                    list.insert(String::from("@list"), Value::Array(expanded_value));
+                    Value::Array(vec![Value::Object(list)])
+                } else {
+                    Value::Array(expanded_value)
+                };
+
+                if term.is_some_and(|t| t.reverse) {
+                    continue;
+                }
+
+                merge_value(&mut result, &expanded_property, expanded_value);
+            }
+
+            if let Some(value) = result.get("@value") {
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                if let Some(Value::Array(types)) = result.get("@type") {
+                    let type_ = types.first().cloned().unwrap_or(Value::Null);
+                    result.insert(String::from("@type"), type_);
+                }
+                return Ok(Value::Object(result));
+            }
+
+            if let Some(set) = result.remove("@set") {
+                return Ok(set);
+            }
+
+            if result.len() == 1 && result.contains_key("@language") {
+                return Ok(Value::Null);
+            }
+
+            if property.is_none() {
+                let free_floating = result.is_empty()
+                    || result.contains_key("@list")
+                    || (result.len() == 1 && result.contains_key("@id"));
+                if free_floating {
+                    return Ok(Value::Null);
+                }
+            }
+
+            Ok(Value::Object(result))
+        }
+
+        scalar => {
+            if property.is_none() {
+                return Ok(Value::Null);
+            }
+            Ok(expand_value(context, property.unwrap(), scalar))
+        }
+    }
+}
+
+fn expand_value(context: &Context, property: &str, value: &Value) -> Value {
+    let definition = context.term(property);
+    let mut result = Map::new();
+
+    match (definition.and_then(|d| d.type_mapping.as_deref()), value) {
+        (Some(mapping @ ("@id" | "@vocab")), Value::String(iri)) => {
+            let expanded = context
+                .expand_iri(iri, mapping == "@vocab", true)
+                .unwrap_or_else(|| iri.clone());
+            result.insert(String::from("@id"), Value::String(expanded));
+            return Value::Object(result);
+        }
+        (Some(mapping), _) if !matches!(mapping, "@id" | "@vocab" | "@none") => {
+            result.insert(String::from("@value"), value.clone());
+            result.insert(String::from("@type"), Value::String(String::from(mapping)));
+            return Value::Object(result);
+        }
+        _ => {}
+    }
+
+    result.insert(String::from("@value"), value.clone());
+    if value.is_string() {
+        let language = match definition.and_then(|d| d.language.as_ref()) {
+            Some(language) => language.as_deref(),
+            None => context.language(),
+        };
+        if let Some(language) = language {
+            result.insert(
+                String::from("@language"),
+                Value::String(String::from(language)),
+            );
+        }
+    }
+
+    Value::Object(result)
+}
+
+fn merge_value(result: &mut Map<String, Value>, key: &str, value: Value) {
+    match (result.get_mut(key), value) {
+        (Some(Value::Array(existing)), Value::Array(more)) => existing.extend(more),
+        (Some(Value::Array(existing)), more) => existing.push(more),
+        (_, value) => {
+            result.insert(String::from(key), value);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactOptions {
+    /// Turn `@id` values into compact IRIs, e.g. `as:Public`. Off by default,
+    /// since most ActivityPub software expects full IRIs there.
+    pub compact_ids: bool,
+}
+
+/// Compact an expanded document against `context`, adding it as `@context`.
+pub fn compact(expanded: &Value, context: &Value) -> Result<Value, JsonLdError> {
+    compact_with(expanded, context, CompactOptions::default())
+}
+
+pub fn compact_with(
+    expanded: &Value,
+    context: &Value,
+    options: CompactOptions,
+) -> Result<Value, JsonLdError> {
+    let active = Context::parse(context)?;
+    let compactor = Compactor {
+        context: &active,
+        options,
+    };
+
+    let compacted = compactor.compact_element(None, expanded)?;
+    let mut result = match compacted {
+        Value::Object(map) => map,
+        Value::Array(items) if items.is_empty() => Map::new(),
+        Value::Array(items) => {
+            let mut map = Map::new();
+            map.insert(compactor.alias("@graph"), Value::Array(items));
+            map
+        }
+        _ => Map::new(),
+    };
+
+    if !result.is_empty() && !context.is_null() {
+        result.insert(String::from("@context"), context.clone());
+    }
+
+    Ok(Value::Object(result))
+}
+
+/// Expand a document against its own context and compact it against
+/// [default_context], so that properties have the names we expect no matter
+/// what the sender called them.
+pub fn normalize(document: &Value) -> Result<Value, JsonLdError> {
+    compact(&expand(document)?, &default_context())
+}
+
+/// [normalize] a document and deserialize it into an Object.
+pub fn to_object(document: &Value) -> Result<crate::Object, JsonLdError> {
+    serde_json::from_value(normalize(document)?).map_err(JsonLdError::Deserialize)
+}
+
+/// Parse a document from another server with [to_object], so renamed or
+/// prefixed terms end up where [Object](crate::Object) expects them.
+///
+/// Documents without a `@context`, or that can't be processed, say because
+/// they use a remote context we have no copy of, are deserialized as they
+/// are instead.
+pub fn parse_object(json: &[u8]) -> Result<crate::Object, serde_json::Error> {
+    let document: Value = serde_json::from_slice(json)?;
+    if document.get("@context").is_some() {
+        if let Ok(object) = to_object(&document) {
+            return Ok(object);
+        }
+    }
+    serde_json::from_value(document)
+}
+
+struct Compactor<'context> {
+    context: &'context Context,
+    options: CompactOptions,
+}
+
+impl Compactor<'_> {
+    fn compact_element(
+        &self,
+        property: Option<&str>,
+        element: &Value,
+    ) -> Result<Value, JsonLdError> {
+        let definition = property.and_then(|property| self.context.term(property));
+        let container = |container: &str| definition.is_some_and(|d| d.has_container(container));
+
+        match element {
+            Value::Array(items) => {
+                let mut result = Vec::new();
+                for item in items {
+                    match self.compact_element(property, item)? {
+                        Value::Null => {}
+                        compacted => result.push(compacted),
+                    }
+                }
+                if result.len() == 1 && !container("@list") && !container("@set") {
+                    Ok(result.pop().unwrap())
+                } else {
+                    Ok(Value::Array(result))
+                }
+            }
+
+            Value::Object(map) => {
+                if map.contains_key("@value") {
+                    return Ok(self.compact_value(definition, map));
+                }
+
+                if let (Some(Value::String(id)), 1) = (map.get("@id"), map.len()) {
+                    match definition.and_then(|d| d.type_mapping.as_deref()) {
+                        Some("@id") => return Ok(Value::String(self.compact_id(id))),
+                        Some("@vocab") => {
+                            return Ok(Value::String(self.compact_iri(id, None, true)))
+                        }
+                        _ => {}
+                    }
+                }
+
+                if let Some(list) = map.get("@list") {
+                    let items = match self.compact_element(property, list)? {
+                        Value::Array(items) => items,
+                        other => vec![other],
+                    };
+                    if container("@list") {
+                        return Ok(Value::Array(items));
+                    }
+                    let mut result = Map::new();
+                    result.insert(self.alias("@list"), Value::Array(items));
+                    return Ok(Value::Object(result));
+                }
+
+                let mut result = Map::new();
+                for (key, value) in map {
+                    match key.as_str() {
+                        "@id" => {
+                            let id = value.as_str().unwrap_or_default();
+                            result.insert(self.alias("@id"), Value::String(self.compact_id(id)));
+                        }
+
+                        "@type" => {
+                            let types = match value {
+                                Value::Array(types) => types.as_slice(),
+                                other => std::slice::from_ref(other),
+                            };
+                            let mut compacted = types
+                                .iter()
+                                .filter_map(|type_| type_.as_str())
+                                .map(|type_| Value::String(self.compact_iri(type_, None, true)))
+                                .collect::<Vec<_>>();
+                            let compacted = if compacted.len() == 1 {
+                                compacted.pop().unwrap()
+                            } else {
+                                Value::Array(compacted)
+                            };
+                            result.insert(self.alias("@type"), compacted);
+                        }
+
+                        keyword if keyword.starts_with('@') => {
+                            result.insert(self.alias(keyword), self.compact_element(None, value)?);
+                        }
+
+                        iri => {
+                            let values = match value {
+                                Value::Array(values) => values.as_slice(),
+                                other => std::slice::from_ref(other),
+                            };
+
+                            if values.is_empty() {
+                                result.insert(
+                                    self.compact_iri(iri, None, true),
+                                    Value::Array(Vec::new()),
+                                );
+                            }
+
+                            for value in values {
+                                let term = self.compact_iri(iri, Some(value), true);
+                                let term_definition = self.context.term(&term);
+                                let term_container = |container: &str| {
+                                    term_definition.is_some_and(|d| d.has_container(container))
+                                };
+
+                                if let (true, Some(Value::String(language))) =
+                                    (term_container("@language"), value.get("@language"))
+                                {
+                                    let map = result
+                                        .entry(term)
+                                        .or_insert_with(|| Value::Object(Map::new()));
+                                    if let Value::Object(map) = map {
+                                        let string =
+                                            value.get("@value").cloned().unwrap_or_default();
+                                        add_value(map, language, string, false);
+                                    }
+                                    continue;
+                                }
+
+                                let compacted = self.compact_element(Some(&term), value)?;
+                                if term_container("@list") {
+                                    result.insert(term, compacted);
+                                } else {
+                                    add_value(
+                                        &mut result,
+                                        &term,
+                                        compacted,
+                                        term_container("@set"),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Ok(Value::Object(result))
+            }
+
+            scalar => Ok(scalar.clone()),
+        }
+    }
+
+    fn compact_value(
+        &self,
+        definition: Option<&TermDefinition>,
+        map: &Map<String, Value>,
+    ) -> Value {
+        let value = map.get("@value").cloned().unwrap_or(Value::Null);
+        let type_ = map.get("@type").and_then(|type_| type_.as_str());
+        let language = map.get("@language").and_then(|language| language.as_str());
+        let extra = map
+            .keys()
+            .any(|key| !matches!(key.as_str(), "@value" | "@type" | "@language"));
+
+        if !extra {
+            let term_type = definition.and_then(|d| d.type_mapping.as_deref());
+            let term_language = match definition.and_then(|d| d.language.as_ref()) {
+                Some(language) => language.as_deref(),
+                None => self.context.language(),
+            };
+
+            let plain = match (type_, language) {
+                (Some("@json"), _) => true,
+                (Some(type_), _) => term_type == Some(type_) || !value.is_string(),
+                // terms without a language of their own, like
+                // preferredUsername, take tagged strings as they are
+                (None, Some(language)) => {
+                    term_type.is_none()
+                        && match term_language {
+                            Some(term) => term.eq_ignore_ascii_case(language),
+                            None => true,
+                        }
+                }
+                (None, None) => true,
+            };
+
+            if plain {
+                return value;
+            }
+        }
+
+        let mut result = Map::new();
+        for (key, value) in map {
+            let value = match (key.as_str(), value) {
+                ("@type", Value::String(type_)) => {
+                    Value::String(self.compact_iri(type_, None, true))
+                }
+                _ => value.clone(),
+            };
+            result.insert(self.alias(key), value);
+        }
+        Value::Object(result)
+    }
+
+    fn alias(&self, keyword: &str) -> String {
+        self.sorted_terms()
+            .into_iter()
+            .find(|(_, definition)| definition.iri.as_deref() == Some(keyword))
+            .map(|(term, _)| String::from(term))
+            .unwrap_or_else(|| String::from(keyword))
+    }
+
+    fn compact_id(&self, id: &str) -> String {
+        if self.options.compact_ids {
+            self.compact_iri(id, None, false)
+        } else {
+            String::from(id)
+        }
+    }
+
+    // shortest first, then alphabetical
+    fn sorted_terms(&self) -> Vec<(&str, &TermDefinition)> {
+        let mut terms = self.context.terms().collect::<Vec<_>>();
+        terms.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then(a.cmp(b)));
+        terms
+    }
+
+    fn compact_iri(&self, iri: &str, value: Option<&Value>, vocab: bool) -> String {
+        if is_keyword(iri) {
+            return self.alias(iri);
+        }
+
+        let terms = self.sorted_terms();
+
+        if vocab {
+            let candidates = terms
+                .iter()
+                .filter(|(_, definition)| {
+                    definition.iri.as_deref() == Some(iri) && !definition.reverse
+                })
+                .collect::<Vec<_>>();
+
+            let selected = match value {
+                Some(value) => candidates
+                    .iter()
+                    .find(|(_, definition)| self.term_fits(definition, value))
+                    .or_else(|| {
+                        candidates
+                            .iter()
+                            .find(|(_, definition)| self.term_fits_loosely(definition, value))
+                    }),
+                None => candidates
+                    .iter()
+                    .find(|(_, definition)| definition.container.is_empty())
+                    .or_else(|| candidates.first()),
+            };
+            if let Some((term, _)) = selected {
+                return String::from(*term);
+            }
+
+            if let Some(suffix) = self
+                .context
+                .vocab()
+                .and_then(|vocab| iri.strip_prefix(vocab))
+                .filter(|suffix| !suffix.is_empty() && self.context.term(suffix).is_none())
+            {
+                return String::from(suffix);
+            }
+        }
+
+        let mut best: Option<String> = None;
+        for (term, definition) in terms {
+            let prefix = match definition.iri.as_deref() {
+                Some(prefix) if definition.prefix => prefix,
+                _ => continue,
+            };
+            let suffix = match iri.strip_prefix(prefix) {
+                Some(suffix) if !suffix.is_empty() => suffix,
+                _ => continue,
+            };
+
+            let candidate = format!("{}:{}", term, suffix);
+            let usable = match self.context.term(&candidate) {
+                None => true,
+                Some(definition) => {
+                    vocab && value.is_none() && definition.iri.as_deref() == Some(iri)
+                }
+            };
+            let shorter = match &best {
+                Some(best) => {
+                    candidate.len() < best.len()
+                        || (candidate.len() == best.len() && candidate < *best)
+                }
+                None => true,
+            };
+            if usable && shorter {
+                best = Some(candidate);
+            }
+        }
+
+        best.unwrap_or_else(|| String::from(iri))
+    }
+
+    fn term_fits(&self, definition: &TermDefinition, value: &Value) -> bool {
+        let is_list = value.get("@list").is_some();
+        if definition.has_container("@list") != is_list {
+            return false;
+        }
+
+        let value_type = value.get("@type").and_then(|type_| type_.as_str());
+        let value_language = value
+            .get("@language")
+            .and_then(|language| language.as_str());
+        let type_mapping = definition.type_mapping.as_deref();
+
+        if definition.has_container("@language") {
+            return value.get("@value").is_some()
+                && value_type.is_none()
+                && value_language.is_some();
+        }
+
+        if value.get("@value").is_some() {
+            return match type_mapping {
+                Some("@id" | "@vocab") => false,
+                Some("@none") | None => {
+                    let term_language = match definition.language.as_ref() {
+                        Some(language) => language.as_deref(),
+                        None => self.context.language(),
+                    };
+                    value_type.is_none()
+                        && match (term_language, value_language) {
+                            (Some(term), Some(value)) => term.eq_ignore_ascii_case(value),
+                            (None, None) => true,
+                            _ => false,
+                        }
+                }
+                Some(type_mapping) => value_type == Some(type_mapping),
+            };
+        }
+
+        definition.language.is_none()
+            && matches!(type_mapping, None | Some("@id" | "@vocab" | "@none"))
+    }
+
+    fn term_fits_loosely(&self, definition: &TermDefinition, value: &Value) -> bool {
+        let is_list = value.get("@list").is_some();
+        !definition.has_container("@language") && definition.has_container("@list") == is_list
+    }
+}
+
+fn add_value(map: &mut Map<String, Value>, key: &str, value: Value, as_array: bool) {
+    match map.get_mut(key) {
+        Some(Value::Array(existing)) => match value {
+            Value::Array(more) => existing.extend(more),
+            value => existing.push(value),
+        },
+        Some(existing) => {
+            let previous = existing.take();
+            let mut values = vec![previous];
+            match value {
+                Value::Array(more) => values.extend(more),
+                value => values.push(value),
+            }
+            *existing = Value::Array(values);
+        }
+        None => {
+            let value = match value {
+                Value::Array(_) => value,
+                value if as_array => Value::Array(vec![value]),
+                value => value,
+            };
+            map.insert(String::from(key), value);
+        }
+    }
+}
+
+pub fn is_keyword(value: &str) -> bool {
+    KEYWORDS.contains(&value)
+}
+
+fn looks_like_keyword(value: &str) -> bool {
+    value.len() > 1 && value.starts_with('@') && value[1..].bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn is_gen_delim(c: char) -> bool {
+    matches!(c, ':' | '/' | '?' | '#' | '[' | ']' | '@')
+}
+
+fn is_absolute_iri(value: &str) -> bool {
+    match value.split_once(':') {
+        Some((scheme, _)) => {
+            let mut chars = scheme.chars();
+            chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const AS: &str = "https://www.w3.org/ns/activitystreams#";
+
+    #[test]
+    fn expands_terms_to_iris() {
+        let expanded = expand(&json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "type": "Note",
+            "id": "https://example.com/notes/1",
+            "content": "hello",
+            "to": "https://www.w3.org/ns/activitystreams#Public"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            expanded,
+            json!([{
+                "@id": "https://example.com/notes/1",
+                "@type": [format!("{}Note", AS)],
+                format!("{}content", AS): [{"@value": "hello"}],
+                format!("{}to", AS): [{"@id": format!("{}Public", AS)}]
+            }])
+        );
+    }
+
+    #[test]
+    fn expands_language_and_aliases() {
+        let expanded = expand(&json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                {"@language": "ja", "body": "as:content", "kind": "@type"}
+            ],
+            "kind": "Note",
+            "body": "こんにちは",
+            "contentMap": {"en": "hello"}
+        }))
+        .unwrap();
+
+        let content = &expanded[0][format!("{}content", AS)];
+        let content = content.as_array().unwrap();
+        assert!(content.contains(&json!({"@value": "こんにちは", "@language": "ja"})));
+        assert!(content.contains(&json!({"@value": "hello", "@language": "en"})));
+        assert_eq!(expanded[0]["@type"], json!([format!("{}Note", AS)]));
+    }
+
+    #[test]
+    fn normalizes_renamed_terms() {
+        let object = to_object(&json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                {"body": "as:content", "author": {"@id": "as:attributedTo", "@type": "@id"}}
+            ],
+            "type": "Note",
+            "body": "hello",
+            "author": "https://example.com/alice"
+        }))
+        .unwrap();
+
+        assert_eq!(object.content.iter().next().unwrap(), "hello");
+        assert_eq!(
+            object
+                .attributed_to
+                .iter()
+                .next()
+                .and_then(crate::LinkObject::href),
+            Some(&Url::parse("https://example.com/alice").unwrap())
+        );
+    }
+
+    #[test]
+    fn parses_objects() {
+        let renamed = br#"{
+            "@context": ["https://www.w3.org/ns/activitystreams", {"body": "as:content"}],
+            "type": "Note",
+            "body": "hello"
+        }"#;
+        let object = parse_object(renamed).unwrap();
+        assert_eq!(object.content.iter().next().unwrap(), "hello");
+        assert!(!object.rest.contains_key("body"));
+
+        // terms without a language of their own keep their tagged values
+        let object = parse_object(include_bytes!("../../eg/9.json")).unwrap();
+        assert_eq!(object.preferred_username.as_deref(), Some("kenzoishii"));
+
+        let object = parse_object(br#"{"type": "Note", "content": "no context"}"#).unwrap();
+        assert_eq!(object.content.iter().next().unwrap(), "no context");
+
+        let object = parse_object(
+            br#"{
+                "@context": ["https://www.w3.org/ns/activitystreams", "https://example.com/unknown"],
+                "type": "Note",
+                "content": "unknown context"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(object.content.iter().next().unwrap(), "unknown context");
+
+        assert!(parse_object(b"not json").is_err());
+    }
+
+    #[test]
+    fn compacts_against_a_context() {
+        let expanded = json!([{
+            "@id": "https://example.com/notes/1",
+            "@type": [format!("{}Note", AS)],
+            format!("{}content", AS): [{"@value": "hello"}],
+            format!("{}to", AS): [{"@id": format!("{}Public", AS)}]
+        }]);
+        let context = json!("https://www.w3.org/ns/activitystreams");
+
+        assert_eq!(
+            compact(&expanded, &context).unwrap(),
+            json!({
+                "@context": "https://www.w3.org/ns/activitystreams",
+                "id": "https://example.com/notes/1",
+                "type": "Note",
+                "content": "hello",
+                "to": "https://www.w3.org/ns/activitystreams#Public"
+            })
+        );
+        let compacted =
+            compact_with(&expanded, &context, CompactOptions { compact_ids: true }).unwrap();
+        assert_eq!(compacted["to"], json!("as:Public"));
+    }
+
+    #[test]
+    fn round_trips_examples() {
+        for example in [
+            include_str!("../../eg/9.json"),
+            include_str!("../../eg/darius.activity.json"),
+            include_str!("../../eg/darius.first.json"),
+            include_str!("../../eg/darius.outbox.json"),
+        ] {
+            let document: Value = serde_json::from_str(example).unwrap();
+            let normalized = normalize(&document).unwrap();
+            assert_eq!(normalize(&normalized).unwrap(), normalized);
+            assert_eq!(normalized["id"], document["id"]);
+            to_object(&document).unwrap();
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            expand(&json!({"@context": "https://example.com/context.jsonld", "name": "x"})),
+            Err(JsonLdError::UnknownRemoteContext(_))
+        ));
+        assert!(matches!(
+            expand(&json!({
+                "@context": [
+                    {"@protected": true, "name": "https://example.com/name"},
+                    {"name": "https://example.com/other"}
+                ],
+                "name": "x"
+            })),
+            Err(JsonLdError::ProtectedTermRedefinition(_))
+        ));
+        assert!(matches!(
+            expand(&json!({"@context": {"@id": "https://example.com/"}})),
+            Err(JsonLdError::KeywordRedefinition(_))
+        ));
+    }
+}
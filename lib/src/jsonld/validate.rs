@@ -0,0 +1,386 @@
+use super::{bundled_context, Context, JsonLdError};
+use crate::LanguageTag;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The ActivityStreams context isn't included anywhere.
+    MissingActivityStreamsContext,
+    /// A remote context we don't have a copy of. Its terms are unknown.
+    UnknownRemoteContext(String),
+    /// A term was defined again with a different meaning.
+    ConflictingTermDefinition {
+        term: String,
+        previous: Option<String>,
+        redefined: Option<String>,
+    },
+    /// A protected term was defined again with a different meaning.
+    ProtectedTermRedefinition(String),
+    /// A term is marked `@protected`.
+    ProtectedTerm(String),
+    /// `@language` sets the default language of plain strings.
+    Language(String),
+    /// `@language` isn't a well-formed BCP47 tag.
+    InvalidLanguage(String),
+    /// `@vocab` sets the IRI that unknown terms expand against.
+    Vocab(Option<String>),
+    /// Anything else that stops the context from being processed.
+    InvalidContext(String),
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::MissingActivityStreamsContext
+            | Diagnostic::ProtectedTermRedefinition(_)
+            | Diagnostic::InvalidContext(_) => Severity::Error,
+
+            Diagnostic::UnknownRemoteContext(_)
+            | Diagnostic::ConflictingTermDefinition { .. }
+            | Diagnostic::InvalidLanguage(_) => Severity::Warning,
+
+            Diagnostic::ProtectedTerm(_) | Diagnostic::Language(_) | Diagnostic::Vocab(_) => {
+                Severity::Info
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::MissingActivityStreamsContext => {
+                write!(
+                    f,
+                    "@context doesn't include {}",
+                    crate::ACTIVITYSTREAMS_CONTEXT
+                )
+            }
+            Diagnostic::UnknownRemoteContext(url) => write!(f, "unknown remote context {}", url),
+            Diagnostic::ConflictingTermDefinition {
+                term,
+                previous,
+                redefined,
+            } => write!(
+                f,
+                "term {:?} redefined from {} to {}",
+                term,
+                previous.as_deref().unwrap_or("null"),
+                redefined.as_deref().unwrap_or("null")
+            ),
+            Diagnostic::ProtectedTermRedefinition(term) => {
+                write!(f, "protected term {:?} redefined", term)
+            }
+            Diagnostic::ProtectedTerm(term) => write!(f, "term {:?} is protected", term),
+            Diagnostic::Language(language) => write!(f, "default language is {}", language),
+            Diagnostic::InvalidLanguage(language) => {
+                write!(f, "default language {:?} is not a BCP47 tag", language)
+            }
+            Diagnostic::Vocab(Some(vocab)) => write!(f, "vocabulary mapping is {}", vocab),
+            Diagnostic::Vocab(None) => write!(f, "vocabulary mapping is cleared"),
+            Diagnostic::InvalidContext(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+/// Diagnostics from validating an `@context`, in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The worst severity of any diagnostic.
+    pub fn severity(&self) -> Option<Severity> {
+        self.iter().map(Diagnostic::severity).max()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.severity() == Some(Severity::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Warning)
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Check the value of an `@context` entry.
+///
+/// Processing continues past problems where possible, so everything wrong
+/// with the context is reported at once.
+pub fn validate_context(context: &Value) -> Diagnostics {
+    let mut validator = Validator {
+        active: Context::new(),
+        diagnostics: Vec::new(),
+        activitystreams: false,
+    };
+    validator.validate(context);
+
+    if !validator.activitystreams {
+        validator
+            .diagnostics
+            .insert(0, Diagnostic::MissingActivityStreamsContext);
+    }
+
+    Diagnostics(validator.diagnostics)
+}
+
+/// Check the `@context` of a document. A document without one is missing the
+/// ActivityStreams context.
+pub fn validate_document(document: &Value) -> Diagnostics {
+    match document.get("@context") {
+        Some(context) => validate_context(context),
+        None => Diagnostics(vec![Diagnostic::MissingActivityStreamsContext]),
+    }
+}
+
+struct Validator {
+    active: Context,
+    diagnostics: Vec<Diagnostic>,
+    activitystreams: bool,
+}
+
+impl Validator {
+    fn validate(&mut self, context: &Value) {
+        match context {
+            Value::Array(contexts) => {
+                for context in contexts {
+                    self.validate(context);
+                }
+            }
+
+            Value::String(url) => {
+                if is_activitystreams(url) {
+                    self.activitystreams = true;
+                }
+                if bundled_context(url).is_none() {
+                    self.diagnostics
+                        .push(Diagnostic::UnknownRemoteContext(url.clone()));
+                    return;
+                }
+                self.process(context);
+            }
+
+            Value::Object(map) => {
+                match map.get("@language") {
+                    Some(Value::String(language)) => match LanguageTag::parse(language) {
+                        Ok(_) => self
+                            .diagnostics
+                            .push(Diagnostic::Language(language.clone())),
+                        Err(_) => self
+                            .diagnostics
+                            .push(Diagnostic::InvalidLanguage(language.clone())),
+                    },
+                    Some(Value::Null) | None => {}
+                    Some(other) => self
+                        .diagnostics
+                        .push(Diagnostic::InvalidLanguage(other.to_string())),
+                }
+
+                if let Some(vocab) = map.get("@vocab") {
+                    self.diagnostics
+                        .push(Diagnostic::Vocab(vocab.as_str().map(String::from)));
+                }
+
+                let previous = self.active.clone();
+                self.process(context);
+
+                for term in map.keys().filter(|term| !term.starts_with('@')) {
+                    let (old, new) = match (previous.term(term), self.active.term(term)) {
+                        (old, Some(new)) => (old, new),
+                        _ => continue,
+                    };
+
+                    if new.protected {
+                        self.diagnostics
+                            .push(Diagnostic::ProtectedTerm(term.clone()));
+                    }
+
+                    match old {
+                        Some(old) if !old.protected && !old.same_definition(new) => self
+                            .diagnostics
+                            .push(Diagnostic::ConflictingTermDefinition {
+                                term: term.clone(),
+                                previous: old.iri.clone(),
+                                redefined: new.iri.clone(),
+                            }),
+                        _ => {}
+                    }
+                }
+            }
+
+            Value::Null => self.process(context),
+
+            other => self.diagnostics.push(Diagnostic::InvalidContext(format!(
+                "context must be null, a string, or an object, not {}",
+                other
+            ))),
+        }
+    }
+
+    fn process(&mut self, context: &Value) {
+        match self.active.process(context) {
+            Ok(active) => self.active = active,
+
+            Err(JsonLdError::ProtectedTermRedefinition(term)) => {
+                self.diagnostics
+                    .push(Diagnostic::ProtectedTermRedefinition(term));
+                // keep going as if the protected terms didn't exist
+                if let Ok(active) = self.active.process_inner(context, &mut Vec::new(), true) {
+                    self.active = active;
+                }
+            }
+
+            Err(JsonLdError::UnknownRemoteContext(url)) => {
+                self.diagnostics.push(Diagnostic::UnknownRemoteContext(url))
+            }
+
+            Err(err) => self
+                .diagnostics
+                .push(Diagnostic::InvalidContext(err.to_string())),
+        }
+    }
+}
+
+fn is_activitystreams(url: &str) -> bool {
+    match (
+        bundled_context(url),
+        bundled_context(crate::ACTIVITYSTREAMS_CONTEXT),
+    ) {
+        (Some(context), Some(activitystreams)) => std::ptr::eq(context, activitystreams),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn examples_are_fine() {
+        for example in [
+            include_str!("../../../eg/9.json"),
+            include_str!("../../../eg/darius.activity.json"),
+            include_str!("../../../eg/darius.outbox.json"),
+        ] {
+            let document: Value = serde_json::from_str(example).unwrap();
+            let diagnostics = validate_document(&document);
+            assert!(
+                diagnostics.severity() <= Some(Severity::Info),
+                "{:?}",
+                diagnostics
+            );
+        }
+    }
+
+    #[test]
+    fn missing_activitystreams() {
+        assert_eq!(
+            validate_document(&json!({"type": "Note"})).0,
+            [Diagnostic::MissingActivityStreamsContext]
+        );
+
+        let diagnostics = validate_context(&json!(["https://w3id.org/security/v1"]));
+        assert!(diagnostics.has_errors());
+        assert_eq!(
+            diagnostics.errors().collect::<Vec<_>>(),
+            [&Diagnostic::MissingActivityStreamsContext]
+        );
+    }
+
+    #[test]
+    fn remote_contexts_and_languages() {
+        let diagnostics = validate_context(&json!([
+            "https://www.w3.org/ns/activitystreams",
+            "https://example.com/context.jsonld",
+            {"@language": "en"},
+            {"@language": "english please"}
+        ]));
+
+        assert_eq!(
+            diagnostics.0,
+            [
+                Diagnostic::UnknownRemoteContext(String::from(
+                    "https://example.com/context.jsonld"
+                )),
+                Diagnostic::Language(String::from("en")),
+                Diagnostic::InvalidLanguage(String::from("english please")),
+            ]
+        );
+        assert_eq!(diagnostics.severity(), Some(Severity::Warning));
+        assert_eq!(diagnostics.warnings().count(), 2);
+    }
+
+    #[test]
+    fn redefined_terms() {
+        let diagnostics = validate_context(&json!([
+            "https://www.w3.org/ns/activitystreams",
+            {"sensitive": "as:sensitive"},
+            {"sensitive": "https://example.com/sensitive"}
+        ]));
+        assert_eq!(
+            diagnostics.0,
+            [Diagnostic::ConflictingTermDefinition {
+                term: String::from("sensitive"),
+                previous: Some(String::from(
+                    "https://www.w3.org/ns/activitystreams#sensitive"
+                )),
+                redefined: Some(String::from("https://example.com/sensitive")),
+            }]
+        );
+
+        let diagnostics = validate_context(&json!([
+            "https://www.w3.org/ns/activitystreams",
+            {"@protected": true, "mine": "https://example.com/mine"},
+            {"mine": "https://example.com/theirs"}
+        ]));
+        assert!(diagnostics
+            .0
+            .contains(&Diagnostic::ProtectedTerm(String::from("mine"))));
+        assert!(diagnostics
+            .0
+            .contains(&Diagnostic::ProtectedTermRedefinition(String::from("mine"))));
+        assert!(diagnostics.has_errors());
+    }
+
+    #[test]
+    fn invalid_contexts() {
+        let diagnostics = validate_context(&json!(["https://www.w3.org/ns/activitystreams", 5]));
+        assert!(matches!(
+            diagnostics.0.as_slice(),
+            [Diagnostic::InvalidContext(_)]
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{LanguageTag, NonFunctional};
+use std::collections::HashMap;
+
+/// An rdf:langString value, like `content` and `contentMap` merged together.
+///
+/// Plain values are tagged with the default language from `@context` if there
+/// is one, and left untagged otherwise. The two properties are also kept as
+/// they were, so [LangString::into_parts] gives them back unchanged.
+///
+/// Map entries whose keys aren't valid language tags are left out of the
+/// values, but still kept in the map.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct LangString {
+    values: Vec<(Option<LanguageTag>, String)>,
+    plain: Vec<String>,
+    map: HashMap<String, String>,
+}
+
+impl LangString {
+    pub fn new(
+        plain: &NonFunctional<String>,
+        map: &HashMap<String, String>,
+        default_language: Option<&LanguageTag>,
+    ) -> LangString {
+        let mut tagged = map
+            .iter()
+            .filter_map(|(language, value)| Some((LanguageTag::parse(language).ok()?, value)))
+            .collect::<Vec<_>>();
+        tagged.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+        let mut values = Vec::new();
+        for value in plain.iter() {
+            // the plain property is usually the same text as one of the map
+            // entries, prefer the tagged one
+            if default_language.is_none() && tagged.iter().any(|(_, tagged)| *tagged == value) {
+                continue;
+            }
+            values.push((default_language.cloned(), value.clone()));
+        }
+
+        for (language, value) in tagged {
+            let entry = (Some(language), value.clone());
+            if !values.contains(&entry) {
+                values.push(entry);
+            }
+        }
+
+        LangString {
+            values,
+            plain: plain.iter().cloned().collect(),
+            map: map.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Add a value. A tagged value replaces any other with the same
+    /// language, since the map only has room for one.
+    pub fn push(&mut self, language: Option<LanguageTag>, value: impl Into<String>) {
+        let value = value.into();
+        match &language {
+            Some(language) => {
+                // written differently, like `en-us` for `en-US`
+                self.map
+                    .retain(|tag, _| !LanguageTag::parse(tag).is_ok_and(|tag| tag == *language));
+                self.map
+                    .insert(String::from(language.as_str()), value.clone());
+                self.values
+                    .retain(|(tag, _)| tag.as_ref() != Some(language));
+            }
+            None => self.plain.push(value.clone()),
+        }
+        self.values.push((language, value));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&LanguageTag>, &str)> {
+        self.values
+            .iter()
+            .map(|(language, value)| (language.as_ref(), value.as_str()))
+    }
+
+    /// The value tagged with exactly `language`.
+    pub fn get(&self, language: &LanguageTag) -> Option<&str> {
+        self.iter()
+            .find(|(tag, _)| *tag == Some(language))
+            .map(|(_, value)| value)
+    }
+
+    /// The first value without a language tag.
+    pub fn untagged(&self) -> Option<&str> {
+        self.iter()
+            .find(|(tag, _)| tag.is_none())
+            .map(|(_, value)| value)
+    }
+
+    /// The value that best matches a list of language ranges, most preferred
+    /// first, using RFC 4647 basic filtering.
+    ///
+    /// Falls back to an untagged value, then to any value at all.
+    pub fn best_match<S: AsRef<str>>(&self, preferences: &[S]) -> Option<&str> {
+        for preference in preferences {
+            let matched = self
+                .iter()
+                .find(|(tag, _)| tag.is_some_and(|tag| tag.matches_basic(preference.as_ref())));
+            if let Some((_, value)) = matched {
+                return Some(value);
+            }
+        }
+
+        self.untagged()
+            .or_else(|| self.iter().next().map(|(_, value)| value))
+    }
+
+    /// Like [LangString::best_match], using the value of an `Accept-Language`
+    /// header.
+    pub fn best_match_accept_language(&self, accept_language: &str) -> Option<&str> {
+        self.best_match(&parse_accept_language(accept_language))
+    }
+
+    /// Split back into a plain property and a map property.
+    pub fn into_parts(mut self) -> (NonFunctional<String>, HashMap<String, String>) {
+        let plain = match self.plain.len() {
+            0 => NonFunctional::None,
+            1 => NonFunctional::One(self.plain.pop().unwrap()),
+            _ => NonFunctional::Many(self.plain),
+        };
+
+        (plain, self.map)
+    }
+}
+
+/// Language ranges from an `Accept-Language` header, most preferred first.
+pub fn parse_accept_language(accept_language: &str) -> Vec<String> {
+    let mut ranges = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let language = parts.next()?.trim();
+            if language.is_empty() {
+                return None;
+            }
+
+            let mut quality = 1.0;
+            for param in parts {
+                if let Some(q) = param.trim().strip_prefix("q=") {
+                    quality = q.trim().parse().ok()?;
+                }
+            }
+
+            (quality > 0.0).then(|| (String::from(language), quality))
+        })
+        .collect::<Vec<_>>();
+
+    // stable, so equal weights keep header order
+    ranges.sort_by(|(_, a): &(String, f32), (_, b)| b.total_cmp(a));
+    ranges.into_iter().map(|(language, _)| language).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Object;
+
+    fn tag(tag: &str) -> LanguageTag {
+        LanguageTag::parse(tag).unwrap()
+    }
+
+    fn round_trip(json: &str, properties: &[&str]) {
+        let mut object: Object = serde_json::from_str(json).unwrap();
+        let (content, name, summary) = (
+            object.content_lang(),
+            object.name_lang(),
+            object.summary_lang(),
+        );
+        object.set_content_lang(content);
+        object.set_name_lang(name);
+        object.set_summary_lang(summary);
+
+        let before: serde_json::Value = serde_json::from_str(json).unwrap();
+        let after = serde_json::to_value(&object).unwrap();
+        for property in properties {
+            assert_eq!(before[property], after[property], "{}", property);
+        }
+    }
+
+    #[test]
+    fn default_language() {
+        let json = include_str!("../../eg/9.json");
+        let object: Object = serde_json::from_str(json).unwrap();
+
+        let name = object.name_lang();
+        assert_eq!(name.get(&tag("ja")), Some("石井健蔵"));
+        assert_eq!(name.untagged(), None);
+
+        round_trip(json, &["name", "nameMap", "summary", "summaryMap"]);
+    }
+
+    const MASTODON: &str = r#"{
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "type": "Note",
+        "id": "https://mastodon.example/users/alice/statuses/1",
+        "content": "<p>Hallo Welt</p>",
+        "contentMap": {"de": "<p>Hallo Welt</p>"}
+    }"#;
+
+    #[test]
+    fn duplicated_plain_values() {
+        let object: Object = serde_json::from_str(MASTODON).unwrap();
+
+        let content = object.content_lang();
+        assert_eq!(
+            content.iter().collect::<Vec<_>>(),
+            [(Some(&tag("de")), "<p>Hallo Welt</p>")]
+        );
+
+        let (plain, map) = content.into_parts();
+        assert_eq!(
+            plain.iter().next().map(String::as_str),
+            Some("<p>Hallo Welt</p>")
+        );
+        assert_eq!(map.get("de").map(String::as_str), Some("<p>Hallo Welt</p>"));
+
+        round_trip(MASTODON, &["content", "contentMap"]);
+    }
+
+    #[test]
+    fn best_match() {
+        let mut content = LangString::default();
+        content.push(None, "hello");
+        content.push(Some(tag("de")), "hallo");
+        content.push(Some(tag("en-GB")), "hello, mate");
+
+        // basic filtering doesn't fall back from de-AT to de
+        assert_eq!(content.best_match(&["de-AT"]), Some("hello"));
+        assert_eq!(content.best_match(&["de"]), Some("hallo"));
+        assert_eq!(content.best_match(&["en"]), Some("hello, mate"));
+        assert_eq!(content.best_match(&["fr"]), Some("hello"));
+        assert_eq!(
+            content.best_match_accept_language("fr;q=0.9, de;q=0.8, *;q=0.1"),
+            Some("hallo")
+        );
+
+        let (plain, map) = content.into_parts();
+        assert_eq!(plain.iter().collect::<Vec<_>>(), ["hello"]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn push_replaces_languages() {
+        let object: Object = serde_json::from_value(serde_json::json!({
+            "type": "Note",
+            "content": "hello",
+            "contentMap": {"en-us": "hello", "de": "hallo"}
+        }))
+        .unwrap();
+        let mut content = object.content_lang();
+        content.push(Some(tag("en-US")), "howdy");
+        content.push(Some(tag("de")), "servus");
+
+        assert_eq!(content.get(&tag("en-US")), Some("howdy"));
+        assert_eq!(content.get(&tag("de")), Some("servus"));
+        assert_eq!(content.iter().count(), 2);
+
+        let (_, map) = content.into_parts();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("en-US").map(String::as_str), Some("howdy"));
+        assert_eq!(map.get("de").map(String::as_str), Some("servus"));
+    }
+
+    #[test]
+    fn invalid_map_languages() {
+        let json = r#"{
+            "type": "Note",
+            "content": "hello",
+            "contentMap": {"en": "hello", "not a language": "hallo"}
+        }"#;
+        let object: Object = serde_json::from_str(json).unwrap();
+
+        let content = object.content_lang();
+        assert_eq!(
+            content.iter().collect::<Vec<_>>(),
+            [(Some(&tag("en")), "hello")]
+        );
+        round_trip(json, &["content", "contentMap"]);
+    }
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(
+            parse_accept_language("en;q=0.5, de, fr;q=0, ja;q=0.5"),
+            ["de", "en", "ja"]
+        );
+        assert_eq!(parse_accept_language(""), Vec::<String>::new());
+    }
+}
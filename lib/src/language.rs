@@ -0,0 +1,292 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageTagError {
+    input: String,
+    reason: String,
+}
+
+impl std::fmt::Display for LanguageTagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid BCP47 language tag {:?}: {}",
+            self.input, self.reason
+        )
+    }
+}
+
+impl std::error::Error for LanguageTagError {}
+
+/// A well-formed BCP47 language tag, like `en-US` or `ja`.
+///
+/// Subtags are normalized to their conventional case when parsed, so `en-us`
+/// and `en-US` are the same tag.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct LanguageTag(language_tags::LanguageTag);
+
+impl LanguageTag {
+    pub fn parse(input: &str) -> Result<LanguageTag, LanguageTagError> {
+        language_tags::LanguageTag::parse(input)
+            .map(LanguageTag)
+            .map_err(|err| LanguageTagError {
+                input: String::from(input),
+                reason: err.to_string(),
+            })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn primary_language(&self) -> &str {
+        self.0.primary_language()
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        self.0.script()
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.0.region()
+    }
+
+    /// Check the subtags against the IANA Language Subtag Registry.
+    pub fn validate(&self) -> Result<(), LanguageTagError> {
+        self.0.validate().map_err(|err| LanguageTagError {
+            input: String::from(self.as_str()),
+            reason: err.to_string(),
+        })
+    }
+
+    /// Replace deprecated and redundant subtags with their preferred values,
+    /// e.g. `iw` becomes `he` and `en-Latn` becomes `en`.
+    pub fn canonicalize(&self) -> Result<LanguageTag, LanguageTagError> {
+        self.0
+            .canonicalize()
+            .map(LanguageTag)
+            .map_err(|err| LanguageTagError {
+                input: String::from(self.as_str()),
+                reason: err.to_string(),
+            })
+    }
+
+    /// RFC 4647 basic filtering: the range `de` matches `de` and `de-CH`, but
+    /// `de-CH` doesn't match `de-Latn-CH`. `*` matches everything.
+    pub fn matches_basic(&self, range: &str) -> bool {
+        if range == "*" {
+            return true;
+        }
+
+        let tag = self.as_str();
+        tag.eq_ignore_ascii_case(range)
+            || (tag.len() > range.len()
+                && tag.as_bytes()[range.len()] == b'-'
+                && tag[..range.len()].eq_ignore_ascii_case(range))
+    }
+
+    /// RFC 4647 extended filtering: like basic filtering, but `*` may appear
+    /// anywhere in the range and subtags may be skipped, so `de-*-DE` and
+    /// `de-DE` both match `de-Latn-DE`.
+    pub fn matches_extended(&self, range: &str) -> bool {
+        let mut range = range.split('-');
+        let mut tag = self.as_str().split('-');
+
+        match (range.next(), tag.next()) {
+            (Some("*"), Some(_)) => {}
+            (Some(range), Some(tag)) if range.eq_ignore_ascii_case(tag) => {}
+            _ => return false,
+        }
+
+        let mut tag = tag.peekable();
+        for subtag in range {
+            if subtag == "*" {
+                continue;
+            }
+
+            loop {
+                match tag.peek() {
+                    None => return false,
+                    Some(next) if next.eq_ignore_ascii_case(subtag) => {
+                        tag.next();
+                        break;
+                    }
+                    Some(next) if next.len() == 1 => return false,
+                    Some(_) => {
+                        tag.next();
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// RFC 4647 lookup: the tag that best matches a list of language ranges, most
+/// preferred first.
+///
+/// Each range is progressively truncated (`zh-Hant-CN`, `zh-Hant`, `zh`) until
+/// one of the tags matches it exactly.
+pub fn lookup<'tag, S: AsRef<str>>(
+    ranges: &[S],
+    tags: impl IntoIterator<Item = &'tag LanguageTag> + Clone,
+) -> Option<&'tag LanguageTag> {
+    for range in ranges {
+        let mut range = range.as_ref();
+        if range == "*" {
+            continue;
+        }
+
+        loop {
+            if let Some(tag) = tags
+                .clone()
+                .into_iter()
+                .find(|tag| tag.as_str().eq_ignore_ascii_case(range))
+            {
+                return Some(tag);
+            }
+
+            match range.rfind('-') {
+                Some(index) => {
+                    range = &range[..index];
+                    // drop a trailing singleton along with it
+                    if range.len() >= 2 && range.as_bytes()[range.len() - 2] == b'-' {
+                        range = &range[..range.len() - 2];
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    None
+}
+
+impl std::str::FromStr for LanguageTag {
+    type Err = LanguageTagError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LanguageTag::parse(s)
+    }
+}
+
+impl std::fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for LanguageTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LanguageTag").field(&self.as_str()).finish()
+    }
+}
+
+impl serde::Serialize for LanguageTag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for LanguageTag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct LanguageTagVisitor;
+        impl<'de> serde::de::Visitor<'de> for LanguageTagVisitor {
+            type Value = LanguageTag;
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "BCP47 language tag")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                LanguageTag::parse(v).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(LanguageTagVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag: &str) -> LanguageTag {
+        LanguageTag::parse(tag).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let tag = tag("zh-hant-tw");
+        assert_eq!(tag.as_str(), "zh-Hant-TW");
+        assert_eq!(tag.primary_language(), "zh");
+        assert_eq!(tag.script(), Some("Hant"));
+        assert_eq!(tag.region(), Some("TW"));
+
+        assert!(LanguageTag::parse("").is_err());
+        assert!(LanguageTag::parse("not a tag").is_err());
+        assert!(LanguageTag::parse("en_US").is_err());
+    }
+
+    #[test]
+    fn validate_and_canonicalize() {
+        assert!(tag("de-CH").validate().is_ok());
+        assert!(tag("xx-YY").validate().is_err());
+        assert_eq!(tag("iw").canonicalize().unwrap(), self::tag("he"));
+    }
+
+    #[test]
+    fn basic_filtering() {
+        assert!(tag("de-CH").matches_basic("de"));
+        assert!(tag("de").matches_basic("DE"));
+        assert!(tag("de").matches_basic("*"));
+        assert!(!tag("de").matches_basic("de-CH"));
+        assert!(!tag("de-Latn-CH").matches_basic("de-CH"));
+        assert!(!tag("den").matches_basic("de"));
+    }
+
+    #[test]
+    fn extended_filtering() {
+        // the examples from RFC 4647 section 3.3.2
+        for matching in [
+            "de-DE",
+            "de-de",
+            "de-Latn-DE",
+            "de-Latf-DE",
+            "de-DE-x-goethe",
+            "de-Latn-DE-1996",
+            "de-Deva-DE",
+        ] {
+            assert!(tag(matching).matches_extended("de-*-DE"), "{}", matching);
+            assert!(tag(matching).matches_extended("de-DE"), "{}", matching);
+        }
+        for other in ["de", "de-x-DE", "de-Deva"] {
+            assert!(!tag(other).matches_extended("de-*-DE"), "{}", other);
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let tags = [tag("en"), tag("zh-Hant"), tag("de-CH")];
+        assert_eq!(
+            super::lookup(&["zh-Hant-CN-x-private"], &tags),
+            Some(&tags[1])
+        );
+        assert_eq!(super::lookup(&["fr", "de-CH-1996"], &tags), Some(&tags[2]));
+        assert_eq!(super::lookup(&["en-GB"], &tags), Some(&tags[0]));
+        assert_eq!(super::lookup(&["*", "fr"], &tags), None);
+    }
+
+    #[test]
+    fn serde() {
+        let tag: LanguageTag = serde_json::from_str(r#""en-gb""#).unwrap();
+        assert_eq!(serde_json::to_string(&tag).unwrap(), r#""en-GB""#);
+        assert!(serde_json::from_str::<LanguageTag>(r#""en_GB""#).is_err());
+    }
+}
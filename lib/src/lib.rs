@@ -1,7 +1,22 @@
+pub mod datetime;
+pub mod duration;
+pub mod jsonld;
+pub mod langstring;
+pub mod language;
+pub mod security;
+
+pub use datetime::DateTime;
+pub use duration::Duration;
+pub use langstring::LangString;
+pub use language::LanguageTag;
+pub use security::{
+    ActorKey, Multikey, MultikeyObject, PublicKey, PublicKeyObject, SigningKey, VerifyingKey,
+};
+
 use std::collections::HashMap;
 use url::Url;
 
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ActivityStreamsType {
     Object,
     Link,
@@ -65,25 +80,394 @@ pub enum ActivityStreamsType {
 
     // extended link types
     Mention,
+
+    // extension types, either a term like Emoji or a full IRI
+    Other(String),
+}
+
+impl ActivityStreamsType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ActivityStreamsType::Object => "Object",
+            ActivityStreamsType::Link => "Link",
+            ActivityStreamsType::Collection => "Collection",
+            ActivityStreamsType::OrderedCollection => "OrderedCollection",
+            ActivityStreamsType::CollectionPage => "CollectionPage",
+            ActivityStreamsType::OrderedCollectionPage => "OrderedCollectionPage",
+            ActivityStreamsType::Activity => "Activity",
+            ActivityStreamsType::Add => "Add",
+            ActivityStreamsType::Announce => "Announce",
+            ActivityStreamsType::Undo => "Undo",
+            ActivityStreamsType::Update => "Update",
+            ActivityStreamsType::View => "View",
+            ActivityStreamsType::Block => "Block",
+            ActivityStreamsType::Create => "Create",
+            ActivityStreamsType::Delete => "Delete",
+            ActivityStreamsType::Dislike => "Dislike",
+            ActivityStreamsType::Flag => "Flag",
+            ActivityStreamsType::Follow => "Follow",
+            ActivityStreamsType::Ignore => "Ignore",
+            ActivityStreamsType::Join => "Join",
+            ActivityStreamsType::Leave => "Leave",
+            ActivityStreamsType::Like => "Like",
+            ActivityStreamsType::Listen => "Listen",
+            ActivityStreamsType::Move => "Move",
+            ActivityStreamsType::Read => "Read",
+            ActivityStreamsType::Remove => "Remove",
+            ActivityStreamsType::Offer => "Offer",
+            ActivityStreamsType::Invite => "Invite",
+            ActivityStreamsType::Reject => "Reject",
+            ActivityStreamsType::TentativeReject => "TentativeReject",
+            ActivityStreamsType::Accept => "Accept",
+            ActivityStreamsType::TentativeAccept => "TentativeAccept",
+            ActivityStreamsType::Arrive => "Arrive",
+            ActivityStreamsType::IntransitiveActivity => "IntransitiveActivity",
+            ActivityStreamsType::Travel => "Travel",
+            ActivityStreamsType::Question => "Question",
+            ActivityStreamsType::Actor => "Actor",
+            ActivityStreamsType::Application => "Application",
+            ActivityStreamsType::Group => "Group",
+            ActivityStreamsType::Organization => "Organization",
+            ActivityStreamsType::Person => "Person",
+            ActivityStreamsType::Service => "Service",
+            ActivityStreamsType::Article => "Article",
+            ActivityStreamsType::Audio => "Audio",
+            ActivityStreamsType::Document => "Document",
+            ActivityStreamsType::Event => "Event",
+            ActivityStreamsType::Image => "Image",
+            ActivityStreamsType::Note => "Note",
+            ActivityStreamsType::Page => "Page",
+            ActivityStreamsType::Place => "Place",
+            ActivityStreamsType::Profile => "Profile",
+            ActivityStreamsType::Relationship => "Relationship",
+            ActivityStreamsType::Tombstone => "Tombstone",
+            ActivityStreamsType::Video => "Video",
+            ActivityStreamsType::Mention => "Mention",
+            ActivityStreamsType::Other(other) => other,
+        }
+    }
+
+    pub fn is_extension(&self) -> bool {
+        matches!(self, ActivityStreamsType::Other(_))
+    }
+
+    /// Activity and its subtypes. Question is left out since it's almost
+    /// always used as a poll object rather than as an activity.
+    pub fn is_activity(&self) -> bool {
+        matches!(
+            self,
+            ActivityStreamsType::Activity
+                | ActivityStreamsType::Add
+                | ActivityStreamsType::Announce
+                | ActivityStreamsType::Undo
+                | ActivityStreamsType::Update
+                | ActivityStreamsType::View
+                | ActivityStreamsType::Block
+                | ActivityStreamsType::Create
+                | ActivityStreamsType::Delete
+                | ActivityStreamsType::Dislike
+                | ActivityStreamsType::Flag
+                | ActivityStreamsType::Follow
+                | ActivityStreamsType::Ignore
+                | ActivityStreamsType::Join
+                | ActivityStreamsType::Leave
+                | ActivityStreamsType::Like
+                | ActivityStreamsType::Listen
+                | ActivityStreamsType::Move
+                | ActivityStreamsType::Read
+                | ActivityStreamsType::Remove
+                | ActivityStreamsType::Offer
+                | ActivityStreamsType::Invite
+                | ActivityStreamsType::Reject
+                | ActivityStreamsType::TentativeReject
+                | ActivityStreamsType::Accept
+                | ActivityStreamsType::TentativeAccept
+                | ActivityStreamsType::Arrive
+                | ActivityStreamsType::IntransitiveActivity
+                | ActivityStreamsType::Travel
+        )
+    }
+}
+
+impl From<&str> for ActivityStreamsType {
+    fn from(name: &str) -> Self {
+        match name {
+            "Object" => ActivityStreamsType::Object,
+            "Link" => ActivityStreamsType::Link,
+            "Collection" => ActivityStreamsType::Collection,
+            "OrderedCollection" => ActivityStreamsType::OrderedCollection,
+            "CollectionPage" => ActivityStreamsType::CollectionPage,
+            "OrderedCollectionPage" => ActivityStreamsType::OrderedCollectionPage,
+            "Activity" => ActivityStreamsType::Activity,
+            "Add" => ActivityStreamsType::Add,
+            "Announce" => ActivityStreamsType::Announce,
+            "Undo" => ActivityStreamsType::Undo,
+            "Update" => ActivityStreamsType::Update,
+            "View" => ActivityStreamsType::View,
+            "Block" => ActivityStreamsType::Block,
+            "Create" => ActivityStreamsType::Create,
+            "Delete" => ActivityStreamsType::Delete,
+            "Dislike" => ActivityStreamsType::Dislike,
+            "Flag" => ActivityStreamsType::Flag,
+            "Follow" => ActivityStreamsType::Follow,
+            "Ignore" => ActivityStreamsType::Ignore,
+            "Join" => ActivityStreamsType::Join,
+            "Leave" => ActivityStreamsType::Leave,
+            "Like" => ActivityStreamsType::Like,
+            "Listen" => ActivityStreamsType::Listen,
+            "Move" => ActivityStreamsType::Move,
+            "Read" => ActivityStreamsType::Read,
+            "Remove" => ActivityStreamsType::Remove,
+            "Offer" => ActivityStreamsType::Offer,
+            "Invite" => ActivityStreamsType::Invite,
+            "Reject" => ActivityStreamsType::Reject,
+            "TentativeReject" => ActivityStreamsType::TentativeReject,
+            "Accept" => ActivityStreamsType::Accept,
+            "TentativeAccept" => ActivityStreamsType::TentativeAccept,
+            "Arrive" => ActivityStreamsType::Arrive,
+            "IntransitiveActivity" => ActivityStreamsType::IntransitiveActivity,
+            "Travel" => ActivityStreamsType::Travel,
+            "Question" => ActivityStreamsType::Question,
+            "Actor" => ActivityStreamsType::Actor,
+            "Application" => ActivityStreamsType::Application,
+            "Group" => ActivityStreamsType::Group,
+            "Organization" => ActivityStreamsType::Organization,
+            "Person" => ActivityStreamsType::Person,
+            "Service" => ActivityStreamsType::Service,
+            "Article" => ActivityStreamsType::Article,
+            "Audio" => ActivityStreamsType::Audio,
+            "Document" => ActivityStreamsType::Document,
+            "Event" => ActivityStreamsType::Event,
+            "Image" => ActivityStreamsType::Image,
+            "Note" => ActivityStreamsType::Note,
+            "Page" => ActivityStreamsType::Page,
+            "Place" => ActivityStreamsType::Place,
+            "Profile" => ActivityStreamsType::Profile,
+            "Relationship" => ActivityStreamsType::Relationship,
+            "Tombstone" => ActivityStreamsType::Tombstone,
+            "Video" => ActivityStreamsType::Video,
+            "Mention" => ActivityStreamsType::Mention,
+            other => ActivityStreamsType::Other(String::from(other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ActivityStreamsType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for ActivityStreamsType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ActivityStreamsType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ActivityStreamsTypeVisitor;
+        impl<'de> serde::de::Visitor<'de> for ActivityStreamsTypeVisitor {
+            type Value = ActivityStreamsType;
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "type name or IRI")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(ActivityStreamsType::from(v))
+            }
+        }
+
+        deserializer.deserialize_str(ActivityStreamsTypeVisitor)
+    }
+}
+
+/// The `type` of an object, which may be more than one type.
+///
+/// Never empty. The primary type is the first type from the ActivityStreams
+/// vocabulary, or the first type if they are all extension types.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ActivityStreamsTypes {
+    types: Vec<ActivityStreamsType>,
+}
+
+impl ActivityStreamsTypes {
+    pub fn new(type_: ActivityStreamsType) -> Self {
+        ActivityStreamsTypes { types: vec![type_] }
+    }
+
+    pub fn primary(&self) -> &ActivityStreamsType {
+        self.types
+            .iter()
+            .find(|type_| !type_.is_extension())
+            .unwrap_or(&self.types[0])
+    }
+
+    pub fn contains(&self, type_: &ActivityStreamsType) -> bool {
+        self.types.contains(type_)
+    }
+
+    pub fn push(&mut self, type_: ActivityStreamsType) {
+        if !self.contains(&type_) {
+            self.types.push(type_);
+        }
+    }
+
+    // there's no is_empty, there's always at least one type
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ActivityStreamsType> {
+        self.types.iter()
+    }
+}
+
+impl From<ActivityStreamsType> for ActivityStreamsTypes {
+    fn from(type_: ActivityStreamsType) -> Self {
+        ActivityStreamsTypes::new(type_)
+    }
+}
+
+impl std::fmt::Debug for ActivityStreamsTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.types.as_slice() {
+            [one] => one.fmt(f),
+            many => f.debug_list().entries(many).finish(),
+        }
+    }
+}
+
+impl serde::Serialize for ActivityStreamsTypes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.types.as_slice() {
+            [one] => one.serialize(serializer),
+            many => many.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ActivityStreamsTypes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(ActivityStreamsType),
+            Many(Vec<ActivityStreamsType>),
+        }
+
+        match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(one) => Ok(ActivityStreamsTypes::new(one)),
+            OneOrMany::Many(many) if many.is_empty() => {
+                Err(serde::de::Error::custom("type must not be empty"))
+            }
+            OneOrMany::Many(many) => {
+                let mut types = ActivityStreamsTypes::new(many[0].clone());
+                for type_ in many.into_iter().skip(1) {
+                    types.push(type_);
+                }
+                Ok(types)
+            }
+        }
+    }
 }
 
 pub const ACTIVITYSTREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
 
-#[derive(serde::Serialize, serde::Deserialize)]
+/// The special collection for addressing everyone.
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum ActivityStreamsContext {
     Url(Url),
     String(String),
-    Map(HashMap<String, ActivityStreamsContext>),
+    Map(ContextMap),
     List(Vec<ActivityStreamsContext>),
 }
 
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ContextMap {
+    /// Kept as written, so an invalid tag is reported by
+    /// [ActivityStreamsContext::validate] rather than failing to deserialize.
+    #[serde(rename = "@language", skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    #[serde(rename = "@vocab", skip_serializing_if = "Option::is_none")]
+    pub vocab: Option<String>,
+
+    #[serde(rename = "@protected", skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
+
+    #[serde(flatten)]
+    pub terms: HashMap<String, ActivityStreamsContext>,
+}
+
+// hreflang is kept as written, like @language, so a bad tag doesn't reject
+// the whole link
+fn parse_hreflang(hreflang: &Option<String>) -> Option<LanguageTag> {
+    hreflang
+        .as_deref()
+        .and_then(|hreflang| LanguageTag::parse(hreflang).ok())
+}
+
+impl ContextMap {
+    /// `@language`, if it's a valid language tag.
+    pub fn language(&self) -> Option<LanguageTag> {
+        self.language
+            .as_deref()
+            .and_then(|language| LanguageTag::parse(language).ok())
+    }
+}
+
 impl Default for ActivityStreamsContext {
     fn default() -> Self {
         ActivityStreamsContext::Url(Url::parse(ACTIVITYSTREAMS_CONTEXT).unwrap())
     }
 }
 
+impl ActivityStreamsContext {
+    /// The default language set with `@language`, if any. Invalid tags are
+    /// ignored.
+    pub fn language(&self) -> Option<LanguageTag> {
+        match self {
+            ActivityStreamsContext::Map(map) => map.language(),
+            // later contexts take precedence
+            ActivityStreamsContext::List(list) => {
+                list.iter().rev().find_map(|context| context.language())
+            }
+            _ => None,
+        }
+    }
+
+    /// Check for a missing ActivityStreams context, unknown remote contexts,
+    /// conflicting or protected terms, and so on.
+    pub fn validate(&self) -> jsonld::Diagnostics {
+        match serde_json::to_value(self) {
+            Ok(context) => jsonld::validate_context(&context),
+            Err(err) => {
+                jsonld::Diagnostics(vec![jsonld::Diagnostic::InvalidContext(err.to_string())])
+            }
+        }
+    }
+}
+
 impl std::fmt::Debug for ActivityStreamsContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -99,7 +483,7 @@ impl std::fmt::Debug for ActivityStreamsContext {
     }
 }
 
-#[derive(Default, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(untagged)]
 pub enum NonFunctional<T> {
     #[default]
@@ -174,51 +558,233 @@ impl<'nf, T> Iterator for NonFunctionalIterMut<'nf, T> {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum ClosedProperty {
-    String(String), // TODO datetime
     Bool(bool),
     Object(LinkObject),
+    DateTime(DateTime),
 }
 
-#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct EndpointsProperty {
-    proxy_url: Option<Url>,
-    oauth_authorization_endpoint: Option<Url>,
-    oauth_token_endpoint: Option<Url>,
-    provide_client_key: Option<Url>,
-    sign_client_key: Option<Url>,
-    shared_inbox: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth_authorization_endpoint: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth_token_endpoint: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provide_client_key: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign_client_key: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_inbox: Option<Url>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum LinkObject {
     Url(Url),
+    Link(Box<Link>),
     Object(Box<Object>),
 }
 
 impl LinkObject {
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            LinkObject::Object(object) => Some(object.as_ref()),
+            _ => None,
+        }
+    }
+
     pub fn as_object_mut(&mut self) -> Option<&mut Object> {
         match self {
             LinkObject::Object(object) => Some(object.as_mut()),
             _ => None,
         }
     }
+
+    pub fn as_link(&self) -> Option<&Link> {
+        match self {
+            LinkObject::Link(link) => Some(link.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The URL this points to: the URL itself, a link's `href`, or an
+    /// object's `id`, falling back to its `url`.
+    pub fn href(&self) -> Option<&Url> {
+        match self {
+            LinkObject::Url(url) => Some(url),
+            LinkObject::Link(link) => Some(&link.href),
+            LinkObject::Object(object) => object
+                .id
+                .as_ref()
+                .or_else(|| object.url.iter().find_map(|url| url.href())),
+        }
+    }
 }
 
 impl std::fmt::Debug for LinkObject {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LinkObject::Url(url) => f.debug_tuple("Link").field(&format!("{}", url)).finish(),
+            LinkObject::Link(link) => link.fmt(f),
             LinkObject::Object(object) => object.fmt(f),
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
+/// A Link, or one of its subtypes like Mention.
+///
+/// Anything with an `href` is deserialized as a Link rather than an Object.
+#[rustfmt::skip]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub schema_context: Option<ActivityStreamsContext>,
+
+    #[serde(rename = "type", default = "Link::default_type")]
+    pub type_: ActivityStreamsTypes,
+
+    pub href: Url,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]        pub id: Option<Url>,
+    #[serde(default, skip_serializing_if = "NonFunctional::is_none")] pub rel: NonFunctional<LinkRelation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]        pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "NonFunctional::is_none")] pub name: NonFunctional<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]      pub name_map: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]        pub hreflang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]        pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]        pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "NonFunctional::is_none")] pub preview: NonFunctional<LinkObject>,
+    #[serde(default, skip_serializing_if = "NonFunctional::is_none")] pub attributed_to: NonFunctional<LinkObject>,
+
+    #[serde(flatten)]
+    pub rest: HashMap<String, serde_json::Value>,
+}
+
+impl Link {
+    pub fn new(href: Url) -> Link {
+        Link {
+            schema_context: None,
+            type_: Link::default_type(),
+            href,
+            id: None,
+            rel: NonFunctional::None,
+            media_type: None,
+            name: NonFunctional::None,
+            name_map: HashMap::default(),
+            hreflang: None,
+            height: None,
+            width: None,
+            preview: NonFunctional::None,
+            attributed_to: NonFunctional::None,
+            rest: HashMap::default(),
+        }
+    }
+
+    fn default_type() -> ActivityStreamsTypes {
+        ActivityStreamsType::Link.into()
+    }
+
+    pub fn has_type(&self, type_: ActivityStreamsType) -> bool {
+        self.type_.contains(&type_)
+    }
+
+    /// `hreflang`, if it's a valid language tag.
+    pub fn hreflang(&self) -> Option<LanguageTag> {
+        parse_hreflang(&self.hreflang)
+    }
+
+    pub fn name_lang(&self) -> LangString {
+        LangString::new(
+            &self.name,
+            &self.name_map,
+            self.schema_context
+                .as_ref()
+                .and_then(|context| context.language())
+                .as_ref(),
+        )
+    }
+}
+
+impl std::fmt::Debug for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut dbg = f.debug_struct("Link");
+
+        if let Some(schema_context) = self.schema_context.as_ref() {
+            dbg.field("@context", schema_context);
+        }
+        dbg.field("type", &self.type_);
+        dbg.field("href", &format!("{}", self.href));
+        if let Some(id) = self.id.as_ref() {
+            dbg.field("id", &format!("{}", id));
+        }
+        match &self.rel {
+            NonFunctional::One(one) => {
+                dbg.field("rel", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("rel", many);
+            }
+            NonFunctional::None => {}
+        }
+        if let Some(media_type) = self.media_type.as_ref() {
+            dbg.field("media_type", &media_type);
+        }
+        match &self.name {
+            NonFunctional::One(one) => {
+                dbg.field("name", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("name", many);
+            }
+            NonFunctional::None => {}
+        }
+        if !self.name_map.is_empty() {
+            dbg.field("name_map", &self.name_map);
+        }
+        if let Some(hreflang) = self.hreflang.as_ref() {
+            dbg.field("hreflang", &hreflang);
+        }
+        if let Some(height) = self.height.as_ref() {
+            dbg.field("height", &height);
+        }
+        if let Some(width) = self.width.as_ref() {
+            dbg.field("width", &width);
+        }
+        match &self.preview {
+            NonFunctional::One(one) => {
+                dbg.field("preview", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("preview", many);
+            }
+            NonFunctional::None => {}
+        }
+        match &self.attributed_to {
+            NonFunctional::One(one) => {
+                dbg.field("attributed_to", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("attributed_to", many);
+            }
+            NonFunctional::None => {}
+        }
+
+        if !self.rest.is_empty() {
+            dbg.field("(rest)", &self.rest);
+        }
+
+        dbg.finish()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub enum LinkRelation {
     Alternate,
@@ -245,9 +811,12 @@ pub enum LinkRelation {
     Search,
     Stylesheet,
     Tag,
+    /// Any other relation, like an extension relation type's URL.
+    #[serde(untagged)]
+    Other(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Units {
     Cm,
     Feet,
@@ -312,14 +881,14 @@ impl<'de> serde::Deserialize<'de> for Units {
 }
 
 #[rustfmt::skip]
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Object {
     #[serde(rename = "@context")]
     pub schema_context: ActivityStreamsContext,
 
     #[serde(rename = "type")]
-    pub type_: ActivityStreamsType,
+    pub type_: ActivityStreamsTypes,
 
     #[serde(skip_serializing_if = "Option::is_none")]        pub id: Option<Url>,
     #[serde(skip_serializing_if = "NonFunctional::is_none")] pub actor: NonFunctional<LinkObject>,
@@ -357,32 +926,37 @@ pub struct Object {
     #[serde(skip_serializing_if = "NonFunctional::is_none")] pub url: NonFunctional<LinkObject>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub accuracy: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub altitude: Option<f32>,
-    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub content: NonFunctional<String>, // TODO - langString/contentMap?
-    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub name: NonFunctional<String>, // TODO - langString/nameMap?
-    #[serde(skip_serializing_if = "Option::is_none")]        pub duration: Option<String>, // TODO - duration
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub content: NonFunctional<String>,
+    // language maps are keyed by tags as written, so one bad tag doesn't
+    // reject the object; see LangString
+    #[serde(skip_serializing_if = "HashMap::is_empty")]      pub content_map: HashMap<String, String>,
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub name: NonFunctional<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]      pub name_map: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]        pub duration: Option<Duration>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub height: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub href: Option<Url>,
-    #[serde(skip_serializing_if = "Option::is_none")]        pub hreflang: Option<String>, // TODO - language tag
+    #[serde(skip_serializing_if = "Option::is_none")]        pub hreflang: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub part_of: Option<LinkObject>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub latitude: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub longitude: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub media_type: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]        pub end_time: Option<String>, // TODO - dateTime
-    #[serde(skip_serializing_if = "Option::is_none")]        pub published: Option<String>, // TODO - dateTime
-    #[serde(skip_serializing_if = "Option::is_none")]        pub start_time: Option<String>, // TODO - dateTime
+    #[serde(skip_serializing_if = "Option::is_none")]        pub end_time: Option<DateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]        pub published: Option<DateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]        pub start_time: Option<DateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub radius: Option<f32>,
     #[serde(skip_serializing_if = "NonFunctional::is_none")] pub rel: NonFunctional<LinkRelation>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub start_index: Option<u32>,
-    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub summary: NonFunctional<String>, // TODO - langString/summaryMap?
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub summary: NonFunctional<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]      pub summary_map: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub total_items: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub units: Option<Units>,
-    #[serde(skip_serializing_if = "Option::is_none")]        pub updated: Option<String>, // TODO - dateTime
+    #[serde(skip_serializing_if = "Option::is_none")]        pub updated: Option<DateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub width: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub subject: Option<LinkObject>,
     #[serde(skip_serializing_if = "NonFunctional::is_none")] pub relationship: NonFunctional<LinkObject>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub describes: Option<Box<Object>>,
-    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub former_type: NonFunctional<LinkObject>,
-    #[serde(skip_serializing_if = "Option::is_none")]        pub deleted: Option<String>, // TODO - dateTime
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub former_type: NonFunctional<ActivityStreamsType>,
+    #[serde(skip_serializing_if = "Option::is_none")]        pub deleted: Option<DateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub source: Option<Box<Object>>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub inbox: Option<LinkObject>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub outbox: Option<LinkObject>,
@@ -392,6 +966,8 @@ pub struct Object {
     #[serde(skip_serializing_if = "NonFunctional::is_none")] pub streams: NonFunctional<LinkObject>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub endpoints: Option<EndpointsProperty>,
     #[serde(skip_serializing_if = "Option::is_none")]        pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub public_key: NonFunctional<PublicKeyObject>,
+    #[serde(skip_serializing_if = "NonFunctional::is_none")] pub assertion_method: NonFunctional<MultikeyObject>,
 
     #[serde(flatten)]
     pub rest: HashMap<String, serde_json::Value>,
@@ -401,7 +977,7 @@ impl Default for Object {
     fn default() -> Self {
         Object {
             schema_context: ActivityStreamsContext::default(),
-            type_: ActivityStreamsType::Object,
+            type_: ActivityStreamsType::Object.into(),
             id: None,
             actor: NonFunctional::None,
             attachment: NonFunctional::None,
@@ -439,7 +1015,9 @@ impl Default for Object {
             accuracy: None,
             altitude: None,
             content: NonFunctional::None,
+            content_map: HashMap::default(),
             name: NonFunctional::None,
+            name_map: HashMap::default(),
             duration: None,
             height: None,
             href: None,
@@ -455,6 +1033,7 @@ impl Default for Object {
             rel: NonFunctional::None,
             start_index: None,
             summary: NonFunctional::None,
+            summary_map: HashMap::default(),
             total_items: None,
             units: None,
             updated: None,
@@ -474,12 +1053,108 @@ impl Default for Object {
             streams: NonFunctional::None,
             endpoints: None,
             preferred_username: None,
+            public_key: NonFunctional::None,
+            assertion_method: NonFunctional::None,
 
             rest: HashMap::default(),
         }
     }
 }
 
+impl Object {
+    pub fn has_type(&self, type_: ActivityStreamsType) -> bool {
+        self.type_.contains(&type_)
+    }
+
+    /// Everything in `to`, `cc`, `bto`, `bcc` and `audience`.
+    pub fn recipients(&self) -> impl Iterator<Item = &Url> {
+        self.to
+            .iter()
+            .chain(self.cc.iter())
+            .chain(self.bto.iter())
+            .chain(self.bcc.iter())
+            .chain(self.audience.iter())
+            .filter_map(LinkObject::href)
+    }
+
+    /// Whether it's addressed to [PUBLIC], or its compact form `as:Public`.
+    pub fn is_public(&self) -> bool {
+        self.recipients()
+            .any(|recipient| matches!(recipient.as_str(), PUBLIC | "as:Public"))
+    }
+
+    /// Keys from both `publicKey` and `assertionMethod`. Entries that only
+    /// reference a key, or that can't be parsed, are skipped.
+    pub fn keys(&self) -> impl Iterator<Item = ActorKey<'_>> {
+        self.public_key
+            .iter()
+            .filter_map(|key| match key {
+                PublicKeyObject::PublicKey(key) => Some(ActorKey::PublicKey(key)),
+                PublicKeyObject::Url(_) | PublicKeyObject::Other(_) => None,
+            })
+            .chain(
+                self.assertion_method
+                    .iter()
+                    .filter_map(|method| match method {
+                        MultikeyObject::Multikey(key) => Some(ActorKey::Multikey(key)),
+                        MultikeyObject::Url(_) | MultikeyObject::Other(_) => None,
+                    }),
+            )
+    }
+
+    /// The key with the given id, like the `keyId` of an HTTP signature.
+    pub fn find_key(&self, id: &Url) -> Option<ActorKey<'_>> {
+        self.keys().find(|key| key.id() == id)
+    }
+
+    /// Validate `@context`. Objects deserialized without one get the default,
+    /// so use [jsonld::validate_document] on the raw JSON to catch that.
+    pub fn validate_context(&self) -> jsonld::Diagnostics {
+        self.schema_context.validate()
+    }
+
+    /// `hreflang`, if it's a valid language tag.
+    pub fn hreflang(&self) -> Option<LanguageTag> {
+        parse_hreflang(&self.hreflang)
+    }
+
+    pub fn content_lang(&self) -> LangString {
+        LangString::new(
+            &self.content,
+            &self.content_map,
+            self.schema_context.language().as_ref(),
+        )
+    }
+
+    pub fn name_lang(&self) -> LangString {
+        LangString::new(
+            &self.name,
+            &self.name_map,
+            self.schema_context.language().as_ref(),
+        )
+    }
+
+    pub fn summary_lang(&self) -> LangString {
+        LangString::new(
+            &self.summary,
+            &self.summary_map,
+            self.schema_context.language().as_ref(),
+        )
+    }
+
+    pub fn set_content_lang(&mut self, content: LangString) {
+        (self.content, self.content_map) = content.into_parts();
+    }
+
+    pub fn set_name_lang(&mut self, name: LangString) {
+        (self.name, self.name_map) = name.into_parts();
+    }
+
+    pub fn set_summary_lang(&mut self, summary: LangString) {
+        (self.summary, self.summary_map) = summary.into_parts();
+    }
+}
+
 impl std::fmt::Debug for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut dbg = f.debug_struct("Object");
@@ -765,6 +1440,9 @@ impl std::fmt::Debug for Object {
             }
             NonFunctional::None => {}
         }
+        if !self.content_map.is_empty() {
+            dbg.field("content_map", &self.content_map);
+        }
         match &self.name {
             NonFunctional::One(one) => {
                 dbg.field("name", &one);
@@ -774,6 +1452,9 @@ impl std::fmt::Debug for Object {
             }
             NonFunctional::None => {}
         }
+        if !self.name_map.is_empty() {
+            dbg.field("name_map", &self.name_map);
+        }
         if let Some(duration) = self.duration.as_ref() {
             dbg.field("duration", &duration);
         }
@@ -831,6 +1512,9 @@ impl std::fmt::Debug for Object {
             }
             NonFunctional::None => {}
         }
+        if !self.summary_map.is_empty() {
+            dbg.field("summary_map", &self.summary_map);
+        }
         if let Some(total_items) = self.total_items.as_ref() {
             dbg.field("total_items", &total_items);
         }
@@ -903,6 +1587,24 @@ impl std::fmt::Debug for Object {
         if let Some(preferred_username) = self.preferred_username.as_ref() {
             dbg.field("preferred_username", &preferred_username);
         }
+        match &self.public_key {
+            NonFunctional::One(one) => {
+                dbg.field("public_key", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("public_key", many);
+            }
+            NonFunctional::None => {}
+        }
+        match &self.assertion_method {
+            NonFunctional::One(one) => {
+                dbg.field("assertion_method", &one);
+            }
+            NonFunctional::Many(many) => {
+                dbg.field("assertion_method", many);
+            }
+            NonFunctional::None => {}
+        }
 
         if !self.rest.is_empty() {
             dbg.field("(rest)", &self.rest);
@@ -911,3 +1613,105 @@ impl std::fmt::Debug for Object {
         dbg.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn invalid_context_languages_are_diagnosed() {
+        let object: Object = serde_json::from_value(json!({
+            "@context": [ACTIVITYSTREAMS_CONTEXT, {"@language": "not a language"}],
+            "type": "Note",
+            "content": "hello"
+        }))
+        .unwrap();
+
+        assert_eq!(object.schema_context.language(), None);
+        assert!(object
+            .validate_context()
+            .0
+            .contains(&jsonld::Diagnostic::InvalidLanguage(String::from(
+                "not a language"
+            ))));
+        let (plain, _) = object.content_lang().into_parts();
+        assert_eq!(plain.iter().next().map(String::as_str), Some("hello"));
+    }
+
+    #[test]
+    fn invalid_hreflangs() {
+        let json = json!({
+            "type": "Link",
+            "href": "https://example.com/about",
+            "hreflang": "not a language"
+        });
+        let link: Link = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(link.hreflang(), None);
+        assert_eq!(serde_json::to_value(&link).unwrap(), json);
+
+        let object: Object = serde_json::from_value(json!({
+            "type": "Note",
+            "hreflang": "en-us"
+        }))
+        .unwrap();
+        assert_eq!(object.hreflang(), LanguageTag::parse("en-US").ok());
+    }
+
+    #[test]
+    fn context_languages() {
+        let object: Object = serde_json::from_value(json!({
+            "@context": [ACTIVITYSTREAMS_CONTEXT, {"@language": "en-us"}],
+            "type": "Note",
+            "content": "hello"
+        }))
+        .unwrap();
+
+        let en_us = LanguageTag::parse("en-US").unwrap();
+        assert_eq!(object.schema_context.language(), Some(en_us.clone()));
+        assert_eq!(object.content_lang().get(&en_us), Some("hello"));
+    }
+
+    #[test]
+    fn types() {
+        let types: ActivityStreamsTypes = serde_json::from_value(json!("Note")).unwrap();
+        assert_eq!(types.len(), 1);
+        assert_eq!(types.primary(), &ActivityStreamsType::Note);
+        assert_eq!(serde_json::to_value(&types).unwrap(), json!("Note"));
+
+        // extension types don't count as the primary type
+        let types: ActivityStreamsTypes =
+            serde_json::from_value(json!(["toot:Emoji", "Image", "Image"])).unwrap();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types.primary(), &ActivityStreamsType::Image);
+        assert_eq!(
+            serde_json::to_value(&types).unwrap(),
+            json!(["toot:Emoji", "Image"])
+        );
+
+        assert!(serde_json::from_value::<ActivityStreamsTypes>(json!([])).is_err());
+    }
+
+    #[test]
+    fn link_relations_round_trip() {
+        let rel = json!([
+            "alternate",
+            "dns-prefetch",
+            "me",
+            "https://example.com/rel/profile"
+        ]);
+        let parsed: NonFunctional<LinkRelation> = serde_json::from_value(rel.clone()).unwrap();
+
+        let parsed = parsed.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            parsed,
+            [
+                LinkRelation::Alternate,
+                LinkRelation::DnsPrefetch,
+                LinkRelation::Other(String::from("me")),
+                LinkRelation::Other(String::from("https://example.com/rel/profile")),
+            ]
+        );
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), rel);
+    }
+}
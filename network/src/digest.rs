@@ -0,0 +1,222 @@
+//! Body digests: the legacy `Digest` header (RFC 3230) and `Content-Digest`
+//! (RFC 9530).
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use request::header::HeaderMap;
+use reqwest as request;
+use sha2::{Digest as _, Sha256, Sha512};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DigestError {
+    /// Neither `Digest` nor `Content-Digest` is present and signed.
+    Missing,
+    Malformed(String),
+    /// None of the algorithms are ones we know.
+    Unsupported(Vec<String>),
+    /// The digest doesn't match the body.
+    Mismatch(String),
+}
+
+impl std::fmt::Display for DigestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DigestError::Missing => write!(f, "no signed Digest or Content-Digest header"),
+            DigestError::Malformed(header) => write!(f, "malformed {} header", header),
+            DigestError::Unsupported(algorithms) => {
+                write!(f, "unsupported digest algorithms {:?}", algorithms)
+            }
+            DigestError::Mismatch(algorithm) => {
+                write!(f, "{} digest doesn't match the body", algorithm)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DigestError {}
+
+/// The value of a `Digest` header for `body`, like `SHA-256=...`.
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(Sha256::digest(body)))
+}
+
+/// The value of a `Content-Digest` header for `body`, like `sha-256=:...:`.
+pub fn content_digest(body: &[u8]) -> String {
+    format!("sha-256=:{}:", STANDARD.encode(Sha256::digest(body)))
+}
+
+fn hash(algorithm: &str, body: &[u8]) -> Option<Vec<u8>> {
+    match algorithm.to_ascii_lowercase().as_str() {
+        "sha-256" => Some(Sha256::digest(body).to_vec()),
+        "sha-512" => Some(Sha512::digest(body).to_vec()),
+        _ => None,
+    }
+}
+
+/// Check `Content-Digest` and `Digest` against the body.
+///
+/// Only the headers in `signed`, the ones the request's signature covers,
+/// are looked at. Every digest in them with an algorithm we know has to
+/// match, and there has to be at least one of them.
+pub fn verify(headers: &HeaderMap, body: &[u8], signed: &[String]) -> Result<(), DigestError> {
+    let signed = |name: &str| {
+        signed
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+    };
+    let mut digests = Vec::new();
+
+    let content_digests = match signed("content-digest") {
+        true => headers.get_all("content-digest").iter().collect(),
+        false => Vec::new(),
+    };
+    for value in content_digests {
+        let value = value
+            .to_str()
+            .map_err(|_| DigestError::Malformed(String::from("Content-Digest")))?;
+        for member in value.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            // structured field dictionary member with a byte sequence value
+            let (algorithm, encoded) = member
+                .split_once('=')
+                .and_then(|(algorithm, value)| {
+                    let value = value.split(';').next()?.trim();
+                    Some((
+                        algorithm.trim(),
+                        value.strip_prefix(':')?.strip_suffix(':')?,
+                    ))
+                })
+                .ok_or_else(|| DigestError::Malformed(String::from("Content-Digest")))?;
+            digests.push((algorithm, encoded));
+        }
+    }
+
+    let legacy_digests = match signed("digest") {
+        true => headers.get_all("digest").iter().collect(),
+        false => Vec::new(),
+    };
+    for value in legacy_digests {
+        let value = value
+            .to_str()
+            .map_err(|_| DigestError::Malformed(String::from("Digest")))?;
+        for member in value.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            let (algorithm, encoded) = member
+                .split_once('=')
+                .ok_or_else(|| DigestError::Malformed(String::from("Digest")))?;
+            digests.push((algorithm.trim(), encoded.trim()));
+        }
+    }
+
+    if digests.is_empty() {
+        return Err(DigestError::Missing);
+    }
+
+    let mut checked = false;
+    for (algorithm, encoded) in digests.iter() {
+        let expected = match hash(algorithm, body) {
+            Some(expected) => expected,
+            None => continue,
+        };
+        let actual = STANDARD
+            .decode(encoded)
+            .map_err(|_| DigestError::Malformed(String::from(*algorithm)))?;
+        if actual != expected {
+            return Err(DigestError::Mismatch(String::from(*algorithm)));
+        }
+        checked = true;
+    }
+
+    if !checked {
+        return Err(DigestError::Unsupported(
+            digests
+                .into_iter()
+                .map(|(algorithm, _)| String::from(algorithm))
+                .collect(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use request::header::HeaderValue;
+
+    const BODY: &[u8] = br#"{"type":"Note"}"#;
+
+    fn with(digests: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in digests {
+            headers.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn signed(headers: &[&str]) -> Vec<String> {
+        headers.iter().map(|header| String::from(*header)).collect()
+    }
+
+    #[test]
+    fn matching_digests() {
+        let headers = with(&[
+            ("digest", &digest(BODY)),
+            ("content-digest", &content_digest(BODY)),
+        ]);
+        assert_eq!(verify(&headers, BODY, &signed(&["digest"])), Ok(()));
+        assert_eq!(verify(&headers, BODY, &signed(&["content-digest"])), Ok(()));
+        assert_eq!(
+            verify(&headers, BODY, &signed(&["Digest", "Content-Digest"])),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn mismatched_digests() {
+        let headers = with(&[("digest", &digest(b"something else"))]);
+        assert_eq!(
+            verify(&headers, BODY, &signed(&["digest"])),
+            Err(DigestError::Mismatch(String::from("SHA-256")))
+        );
+    }
+
+    #[test]
+    fn unsigned_digests_are_ignored() {
+        // a bad digest nobody signed doesn't matter
+        let headers = with(&[
+            ("digest", &digest(b"something else")),
+            ("content-digest", &content_digest(BODY)),
+        ]);
+        assert_eq!(verify(&headers, BODY, &signed(&["content-digest"])), Ok(()));
+
+        // and a good one doesn't count
+        let headers = with(&[("digest", &digest(BODY))]);
+        assert_eq!(
+            verify(&headers, BODY, &signed(&["(request-target)", "host"])),
+            Err(DigestError::Missing)
+        );
+    }
+
+    #[test]
+    fn needs_a_supported_algorithm() {
+        let headers = with(&[("content-digest", "md5=:AAAA:")]);
+        assert_eq!(
+            verify(&headers, BODY, &signed(&["content-digest"])),
+            Err(DigestError::Unsupported(vec![String::from("md5")]))
+        );
+
+        let sha512 = STANDARD.encode(Sha512::digest(BODY));
+        let headers = with(&[(
+            "content-digest",
+            &format!("md5=:AAAA:, sha-512=:{}:", sha512),
+        )]);
+        assert_eq!(verify(&headers, BODY, &signed(&["content-digest"])), Ok(()));
+    }
+
+    #[test]
+    fn malformed_digests() {
+        let headers = with(&[("content-digest", "sha-256=not-a-byte-sequence")]);
+        assert_eq!(
+            verify(&headers, BODY, &signed(&["content-digest"])),
+            Err(DigestError::Malformed(String::from("Content-Digest")))
+        );
+    }
+}
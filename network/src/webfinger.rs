@@ -0,0 +1,481 @@
+//! WebFinger (https://datatracker.ietf.org/doc/html/rfc7033) lookups of
+//! `acct:` URIs.
+
+use crate::Signer;
+use reqwest as request;
+use smacktivity::Object;
+use std::collections::HashMap;
+use url::Url;
+
+pub const JRD_MEDIA_TYPE: &str = "application/jrd+json";
+pub const ACTIVITY_MEDIA_TYPE: &str = "application/activity+json";
+pub const LD_MEDIA_TYPE: &str =
+    "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"";
+pub const PROFILE_PAGE_REL: &str = "http://webfinger.net/rel/profile-page";
+
+/// A JSON Resource Descriptor.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Jrd {
+    pub subject: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, Option<String>>,
+    pub links: Vec<JrdLink>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct JrdLink {
+    pub rel: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub titles: HashMap<String, String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, Option<String>>,
+}
+
+impl Jrd {
+    /// The `self` link to an ActivityPub actor.
+    pub fn actor_link(&self) -> Option<&str> {
+        self.links
+            .iter()
+            .filter(|link| link.rel == "self")
+            .find(|link| {
+                link.type_
+                    .as_deref()
+                    .is_some_and(crate::is_activitystreams_media_type)
+            })
+            .and_then(|link| link.href.as_deref())
+    }
+}
+
+#[derive(Debug)]
+pub enum WebFingerError {
+    /// Not something like `acct:user@host`.
+    InvalidResource(String),
+    /// The request to the WebFinger endpoint failed.
+    Request(String),
+    /// Neither the WebFinger endpoint nor `host-meta` knows the resource.
+    NotFound(String),
+    /// The response wasn't a valid JRD.
+    InvalidJrd(String),
+    /// The JRD has no `self` link with an ActivityPub media type.
+    NoActorLink(String),
+    /// Fetching the actor from the `self` link failed.
+    Actor(String),
+}
+
+impl std::fmt::Display for WebFingerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebFingerError::InvalidResource(resource) => {
+                write!(f, "invalid WebFinger resource {:?}", resource)
+            }
+            WebFingerError::Request(err) => write!(f, "WebFinger request failed: {}", err),
+            WebFingerError::NotFound(resource) => write!(f, "{} not found", resource),
+            WebFingerError::InvalidJrd(err) => write!(f, "invalid JRD: {}", err),
+            WebFingerError::NoActorLink(resource) => {
+                write!(f, "{} has no ActivityPub actor", resource)
+            }
+            WebFingerError::Actor(err) => write!(f, "couldn't fetch actor: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for WebFingerError {}
+
+/// Split `acct:user@host`, `user@host` or `@user@host` into `acct:user@host`
+/// and the host.
+pub fn parse_account(resource: &str) -> Result<(String, String), WebFingerError> {
+    let invalid = || WebFingerError::InvalidResource(String::from(resource));
+
+    let account = resource.trim();
+    let account = account.strip_prefix("acct:").unwrap_or(account);
+    let account = account.strip_prefix('@').unwrap_or(account);
+
+    let (user, host) = account.rsplit_once('@').ok_or_else(invalid)?;
+    if user.is_empty() || host.is_empty() || user.contains('/') || host.contains('/') {
+        return Err(invalid());
+    }
+
+    Ok((format!("acct:{}@{}", user, host), String::from(host)))
+}
+
+/// Resolve `acct:user@host` to an actor, signing the actor fetch with
+/// `signer` if there is one.
+pub async fn webfinger(resource: &str, signer: Option<&Signer>) -> Result<Object, WebFingerError> {
+    let jrd = webfinger_jrd(resource).await?;
+    let actor = jrd
+        .actor_link()
+        .ok_or_else(|| WebFingerError::NoActorLink(jrd.subject.clone()))?;
+
+    crate::request_object_with(actor, signer)
+        .await
+        .map_err(|err| WebFingerError::Actor(err.to_string()))
+}
+
+/// Look up the JRD for `acct:user@host`, falling back to the LRDD template
+/// from `host-meta` if `/.well-known/webfinger` doesn't know it.
+pub async fn webfinger_jrd(resource: &str) -> Result<Jrd, WebFingerError> {
+    let (resource, host) = parse_account(resource)?;
+    let server = Url::parse(&format!("https://{}", host))
+        .map_err(|_| WebFingerError::InvalidResource(resource.clone()))?;
+    lookup(&server, &resource).await
+}
+
+// `resource` on `server`, which is only ever not https in tests
+async fn lookup(server: &Url, resource: &str) -> Result<Jrd, WebFingerError> {
+    let client = request::Client::new();
+    let mut url = server
+        .join("/.well-known/webfinger")
+        .map_err(|_| WebFingerError::InvalidResource(String::from(resource)))?;
+    url.query_pairs_mut().append_pair("resource", resource);
+
+    match fetch_jrd(&client, url.as_str()).await {
+        Ok(Some(jrd)) => return Ok(jrd),
+        Ok(None) => {}
+        // fall back to host-meta below, but report the original failure if
+        // that doesn't work either
+        Err(err) => {
+            return match lrdd(&client, server, resource).await {
+                Ok(Some(jrd)) => Ok(jrd),
+                _ => Err(err),
+            }
+        }
+    }
+
+    lrdd(&client, server, resource)
+        .await?
+        .ok_or_else(|| WebFingerError::NotFound(String::from(resource)))
+}
+
+// None if the server says the resource doesn't exist
+async fn fetch_jrd(client: &request::Client, url: &str) -> Result<Option<Jrd>, WebFingerError> {
+    let response = client
+        .get(url)
+        .header("Accept", JRD_MEDIA_TYPE)
+        .send()
+        .await
+        .map_err(|err| WebFingerError::Request(err.to_string()))?;
+
+    if matches!(
+        response.status(),
+        request::StatusCode::NOT_FOUND | request::StatusCode::GONE
+    ) {
+        return Ok(None);
+    }
+
+    let response = response
+        .error_for_status()
+        .map_err(|err| WebFingerError::Request(err.to_string()))?;
+    let body = response
+        .bytes()
+        .await
+        .map_err(|err| WebFingerError::Request(err.to_string()))?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| WebFingerError::InvalidJrd(err.to_string()))
+}
+
+async fn lrdd(
+    client: &request::Client,
+    server: &Url,
+    resource: &str,
+) -> Result<Option<Jrd>, WebFingerError> {
+    let url = server
+        .join("/.well-known/host-meta")
+        .map_err(|_| WebFingerError::InvalidResource(String::from(resource)))?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| WebFingerError::Request(err.to_string()))?;
+    let host_meta = response
+        .text()
+        .await
+        .map_err(|err| WebFingerError::Request(err.to_string()))?;
+
+    let template = match lrdd_template(&host_meta) {
+        Some(template) => template,
+        None => return Ok(None),
+    };
+
+    let encoded = url::form_urlencoded::byte_serialize(resource.as_bytes()).collect::<String>();
+    fetch_jrd(client, &template.replace("{uri}", &encoded)).await
+}
+
+/// The LRDD template from a `host-meta` document, either XRD or JSON.
+pub fn lrdd_template(host_meta: &str) -> Option<String> {
+    if let Ok(jrd) = serde_json::from_str::<Jrd>(host_meta) {
+        return jrd
+            .links
+            .into_iter()
+            .find(|link| link.rel == "lrdd")
+            .and_then(|link| link.template);
+    }
+
+    host_meta
+        .split('<')
+        .filter(|element| element.starts_with("Link") || element.starts_with("hm:Link"))
+        .find(|element| attribute(element, "rel").as_deref() == Some("lrdd"))
+        .and_then(|element| attribute(element, "template"))
+}
+
+fn attribute(element: &str, name: &str) -> Option<String> {
+    let mut rest = element;
+    loop {
+        let index = rest.find(name)?;
+        let after = rest[index + name.len()..].trim_start();
+        let preceded = rest[..index]
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_ascii_whitespace());
+        rest = &rest[index + name.len()..];
+
+        let after = match after.strip_prefix('=') {
+            Some(after) if preceded => after.trim_start(),
+            _ => continue,
+        };
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &after[1..];
+        let end = value.find(quote)?;
+        return Some(
+            value[..end]
+                .replace("&amp;", "&")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::Query,
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::get,
+        Json, Router,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn accounts() {
+        for resource in [
+            "acct:alice@example.com",
+            "alice@example.com",
+            "@alice@example.com",
+            " acct:alice@example.com ",
+        ] {
+            assert_eq!(
+                parse_account(resource).unwrap(),
+                (
+                    String::from("acct:alice@example.com"),
+                    String::from("example.com")
+                )
+            );
+        }
+        // the host is after the last @
+        assert_eq!(
+            parse_account("acct:alice@home@example.com:8443").unwrap(),
+            (
+                String::from("acct:alice@home@example.com:8443"),
+                String::from("example.com:8443")
+            )
+        );
+
+        for resource in [
+            "alice",
+            "@example.com",
+            "alice@",
+            "https://example.com/@alice",
+        ] {
+            assert!(matches!(
+                parse_account(resource),
+                Err(WebFingerError::InvalidResource(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn actor_links() {
+        let jrd: Jrd = serde_json::from_value(json!({
+            "subject": "acct:alice@example.com",
+            "links": [
+                {"rel": PROFILE_PAGE_REL, "type": "text/html", "href": "https://example.com/@alice"},
+                {"rel": "self", "type": "text/html", "href": "https://example.com/wrong"},
+                {"rel": "self", "type": LD_MEDIA_TYPE, "href": "https://example.com/users/alice"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(jrd.actor_link(), Some("https://example.com/users/alice"));
+
+        let jrd = Jrd {
+            links: vec![JrdLink {
+                rel: String::from("self"),
+                href: Some(String::from("https://example.com/users/alice")),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(jrd.actor_link(), None);
+    }
+
+    #[test]
+    fn xrd_templates() {
+        let host_meta = r#"<?xml version="1.0" encoding="UTF-8"?>
+<XRD xmlns="http://docs.oasis-open.org/ns/xri/xrd-1.0">
+  <Link rel="self" template="https://example.com/wrong?uri={uri}"/>
+  <Link type="application/xrd+xml" rel="lrdd" template="https://example.com/.well-known/webfinger?resource={uri}&amp;format=xrd"/>
+</XRD>"#;
+        assert_eq!(
+            lrdd_template(host_meta).as_deref(),
+            Some("https://example.com/.well-known/webfinger?resource={uri}&format=xrd")
+        );
+
+        let host_meta =
+            "<hm:Link xrel='self' rel='lrdd' template='https://example.com/lrdd?q={uri}'/>";
+        assert_eq!(
+            lrdd_template(host_meta).as_deref(),
+            Some("https://example.com/lrdd?q={uri}")
+        );
+
+        assert_eq!(lrdd_template("<XRD><Link rel=\"self\"/></XRD>"), None);
+        assert_eq!(lrdd_template("not xml at all"), None);
+    }
+
+    #[test]
+    fn json_templates() {
+        let host_meta = json!({
+            "links": [{"rel": "lrdd", "template": "https://example.com/lrdd?uri={uri}"}]
+        })
+        .to_string();
+        assert_eq!(
+            lrdd_template(&host_meta).as_deref(),
+            Some("https://example.com/lrdd?uri={uri}")
+        );
+        assert_eq!(lrdd_template(r#"{"links": []}"#), None);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(
+            attribute(r#"Link rel="lrdd" template="a&lt;b&gt;""#, "template").as_deref(),
+            Some("a<b>")
+        );
+        // only whole attribute names count
+        assert_eq!(attribute(r#"Link xrel="self""#, "rel"), None);
+        assert_eq!(
+            attribute(r#"Link xrel="self" rel = 'lrdd'"#, "rel").as_deref(),
+            Some("lrdd")
+        );
+        assert_eq!(attribute(r#"Link rel="unterminated"#, "rel"), None);
+        assert_eq!(attribute("Link rel=lrdd", "rel"), None);
+    }
+
+    fn jrd(resource: &str) -> Json<serde_json::Value> {
+        Json(json!({
+            "subject": resource,
+            "links": [{"rel": "self", "type": ACTIVITY_MEDIA_TYPE, "href": "https://example.com/users/alice"}]
+        }))
+    }
+
+    /// Serve `router` on a local port, standing in for another server.
+    fn serve(router: Router) -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(router.into_make_service()),
+        );
+        server
+    }
+
+    type Params = Query<HashMap<String, String>>;
+
+    #[tokio::test]
+    async fn looks_up_accounts() {
+        let server = serve(Router::new().route(
+            "/.well-known/webfinger",
+            get(|Query(params): Params| async move {
+                match params["resource"].as_str() {
+                    "acct:alice@example.com" => jrd("acct:alice@example.com").into_response(),
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }),
+        ));
+
+        let found = lookup(&server, "acct:alice@example.com").await.unwrap();
+        assert_eq!(found.actor_link(), Some("https://example.com/users/alice"));
+
+        // without host-meta either, there's nowhere else to look
+        assert!(matches!(
+            lookup(&server, "acct:bob@example.com").await,
+            Err(WebFingerError::Request(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_host_meta() {
+        let server = serve(
+            Router::new()
+                .route(
+                    "/.well-known/webfinger",
+                    get(|| async { StatusCode::NOT_FOUND }),
+                )
+                .route(
+                    "/.well-known/host-meta",
+                    get(|headers: HeaderMap| async move {
+                        let host = headers["host"].to_str().unwrap();
+                        format!(
+                            r#"<XRD><Link rel="lrdd" template="http://{}/lrdd?resource={{uri}}"/></XRD>"#,
+                            host
+                        )
+                    }),
+                )
+                .route(
+                    "/lrdd",
+                    get(|Query(params): Params| async move {
+                        match params["resource"].as_str() {
+                            "acct:alice@example.com" => jrd("acct:alice@example.com").into_response(),
+                            _ => StatusCode::NOT_FOUND.into_response(),
+                        }
+                    }),
+                ),
+        );
+
+        let found = lookup(&server, "acct:alice@example.com").await.unwrap();
+        assert_eq!(found.subject, "acct:alice@example.com");
+        assert!(matches!(
+            lookup(&server, "acct:bob@example.com").await,
+            Err(WebFingerError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reports_the_original_failure() {
+        let server = serve(
+            Router::new()
+                .route("/.well-known/webfinger", get(|| async { "not a JRD" }))
+                .route("/.well-known/host-meta", get(|| async { "<XRD></XRD>" })),
+        );
+
+        assert!(matches!(
+            lookup(&server, "acct:alice@example.com").await,
+            Err(WebFingerError::InvalidJrd(_))
+        ));
+    }
+}
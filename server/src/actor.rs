@@ -0,0 +1,273 @@
+use crate::{
+    negotiate::{wants_activity_json, ActivityJson},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use smacktivity::{
+    jsonld::SECURITY_CONTEXT, ActivityStreamsContext, ActivityStreamsType, ContextMap,
+    EndpointsProperty, LinkObject, NonFunctional, Object, PublicKey, PublicKeyObject,
+    ACTIVITYSTREAMS_CONTEXT,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// The configured user as a Person.
+pub fn actor(state: &AppState) -> Object {
+    let config = &state.config;
+    let actor_id = config.actor_id();
+
+    let icon = match &config.user.icon {
+        Some(icon) => NonFunctional::One(LinkObject::Object(Box::new(Object {
+            type_: ActivityStreamsType::Image.into(),
+            url: NonFunctional::One(LinkObject::Url(icon.clone())),
+            ..Default::default()
+        }))),
+        None => NonFunctional::None,
+    };
+
+    let mut actor = Object {
+        schema_context: ActivityStreamsContext::List(vec![
+            ActivityStreamsContext::String(String::from(ACTIVITYSTREAMS_CONTEXT)),
+            ActivityStreamsContext::String(String::from(SECURITY_CONTEXT)),
+            ActivityStreamsContext::Map(ContextMap {
+                terms: HashMap::from([(
+                    String::from("manuallyApprovesFollowers"),
+                    ActivityStreamsContext::String(String::from("as:manuallyApprovesFollowers")),
+                )]),
+                ..Default::default()
+            }),
+        ]),
+        type_: ActivityStreamsType::Person.into(),
+        id: Some(actor_id.clone()),
+        preferred_username: Some(config.user.username.clone()),
+        name: config
+            .user
+            .name
+            .clone()
+            .map_or(NonFunctional::None, NonFunctional::One),
+        summary: config
+            .user
+            .summary
+            .clone()
+            .map_or(NonFunctional::None, NonFunctional::One),
+        icon,
+        url: NonFunctional::One(LinkObject::Url(config.profile_url())),
+        inbox: Some(LinkObject::Url(config.inbox())),
+        outbox: Some(LinkObject::Url(config.outbox())),
+        followers: Some(LinkObject::Url(config.followers())),
+        following: Some(LinkObject::Url(config.following())),
+        liked: Some(LinkObject::Url(config.liked())),
+        endpoints: Some(EndpointsProperty {
+            shared_inbox: Some(config.shared_inbox()),
+            ..Default::default()
+        }),
+        public_key: NonFunctional::One(PublicKeyObject::PublicKey(Box::new(PublicKey::new(
+            state.signer.key_id().clone(),
+            actor_id,
+            &state.signer.key().verifying_key(),
+        )))),
+        ..Default::default()
+    };
+    actor.rest.insert(
+        String::from("manuallyApprovesFollowers"),
+        serde_json::Value::Bool(config.user.manually_approves_followers),
+    );
+    actor
+}
+
+/// `GET /users/{username}`
+pub async fn get_actor(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if username != state.config.user.username {
+        return (StatusCode::NOT_FOUND, "no such user").into_response();
+    }
+
+    if wants_activity_json(&headers) {
+        ActivityJson(actor(&state)).into_response()
+    } else {
+        profile_page(&state).into_response()
+    }
+}
+
+/// `GET /@{username}`
+pub async fn get_profile(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    match username.strip_prefix('@') {
+        Some(username) if username == state.config.user.username => {}
+        _ => return (StatusCode::NOT_FOUND, "no such user").into_response(),
+    }
+
+    if wants_activity_json(&headers) {
+        ActivityJson(actor(&state)).into_response()
+    } else {
+        profile_page(&state).into_response()
+    }
+}
+
+fn profile_page(state: &AppState) -> Html<String> {
+    let config = &state.config;
+    let handle = format!("@{}@{}", config.user.username, config.domain());
+    let name = config.user.name.as_deref().unwrap_or(&config.user.username);
+
+    let icon = config
+        .user
+        .icon
+        .as_ref()
+        .map(|icon| format!("<img src=\"{}\" alt=\"\">\n", escape(icon.as_str())))
+        .unwrap_or_default();
+    // the summary is HTML already, and comes from our own config
+    let summary = config.user.summary.as_deref().unwrap_or_default();
+
+    Html(format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{name} ({handle})</title>
+<link rel=\"alternate\" type=\"application/activity+json\" href=\"{actor}\">
+</head>
+<body>
+{icon}<h1>{name}</h1>
+<p>{handle}</p>
+{summary}
+</body>
+</html>
+",
+        name = escape(name),
+        handle = escape(&handle),
+        actor = escape(config.actor_id().as_str()),
+        icon = icon,
+        summary = summary,
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use axum::http::header;
+
+    fn accepting(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, accept.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn person() {
+        let state = testing::state();
+        let person = serde_json::to_value(actor(&state)).unwrap();
+        assert_eq!(person["type"], "Person");
+        assert_eq!(person["id"], "https://local.example/users/alice");
+        assert_eq!(person["preferredUsername"], "alice");
+        assert_eq!(person["inbox"], "https://local.example/users/alice/inbox");
+        assert_eq!(
+            person["publicKey"]["id"],
+            "https://local.example/users/alice#main-key"
+        );
+        assert_eq!(person["manuallyApprovesFollowers"], false);
+        assert_eq!(
+            person["@context"][2]["manuallyApprovesFollowers"],
+            "as:manuallyApprovesFollowers"
+        );
+
+        let state = testing::state_with_user("manually_approves_followers = true");
+        let person = serde_json::to_value(actor(&state)).unwrap();
+        assert_eq!(person["manuallyApprovesFollowers"], true);
+    }
+
+    #[tokio::test]
+    async fn negotiates() {
+        let state = testing::state();
+        let get = |accept: &str| {
+            get_actor(
+                State(state.clone()),
+                Path(String::from("alice")),
+                accepting(accept),
+            )
+        };
+
+        let response = get("application/activity+json").await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            smacktivity_network::webfinger::ACTIVITY_MEDIA_TYPE
+        );
+        assert_eq!(testing::json(response).await["type"], "Person");
+
+        let response = get("text/html").await;
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+
+        let response = get_profile(
+            State(state.clone()),
+            Path(String::from("@alice")),
+            accepting("application/activity+json"),
+        )
+        .await;
+        assert_eq!(testing::json(response).await["type"], "Person");
+
+        for response in [
+            get_actor(
+                State(state.clone()),
+                Path(String::from("bob")),
+                HeaderMap::new(),
+            )
+            .await,
+            get_profile(
+                State(state.clone()),
+                Path(String::from("alice")),
+                HeaderMap::new(),
+            )
+            .await,
+        ] {
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn profile_pages_are_escaped() {
+        let state = testing::state_with_user(
+            r#"
+            name = "<script>alert('hi')</script> & \"friends\""
+            icon = "https://local.example/icon.png?a=1&b=\"2\""
+            "#,
+        );
+        let response = get_profile(
+            State(state.clone()),
+            Path(String::from("@alice")),
+            accepting("text/html"),
+        )
+        .await;
+        let page = testing::text(response).await;
+
+        assert!(!page.contains("<script>"));
+        assert!(page.contains(
+            "<h1>&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt; &amp; &quot;friends&quot;</h1>"
+        ));
+        assert!(page.contains("<p>@alice@local.example</p>"));
+        assert!(page.contains(
+            "<link rel=\"alternate\" type=\"application/activity+json\" href=\"https://local.example/users/alice\">"
+        ));
+        assert!(page.contains("icon.png?a=1&amp;b="));
+        assert!(!page.contains("b=\"2\""));
+    }
+}
@@ -0,0 +1,102 @@
+//! Who's asking.
+
+use crate::{storage::StorageError, AppState};
+use axum::http::{header, HeaderMap, Method, Uri};
+use smacktivity::Object;
+use smacktivity_network::VerifyError;
+use url::Url;
+
+pub enum Viewer {
+    /// An unsigned request.
+    Anonymous,
+    /// A request with a valid HTTP signature from this actor.
+    Actor(Url),
+    /// The user, with the bearer token from the config.
+    Owner,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Signature(VerifyError),
+    BadToken,
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Signature(err) => write!(f, "couldn't verify signature: {}", err),
+            AuthError::BadToken => write!(f, "bad bearer token"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl Viewer {
+    /// Check the request's bearer token or signature if it has one. Bad
+    /// credentials are an error rather than falling back to anonymous.
+    pub async fn from_request(
+        state: &AppState,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Result<Viewer, AuthError> {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|authorization| authorization.to_str().ok())
+            .and_then(|authorization| authorization.strip_prefix("Bearer "));
+        if let Some(bearer) = bearer {
+            return match &state.config.user.token {
+                Some(token) if constant_time_eq(bearer.trim().as_bytes(), token.as_bytes()) => {
+                    Ok(Viewer::Owner)
+                }
+                _ => Err(AuthError::BadToken),
+            };
+        }
+
+        if !headers.contains_key("signature") {
+            return Ok(Viewer::Anonymous);
+        }
+
+        let path = uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+        let actor = state
+            .verifier
+            .verify(method.as_str(), path, headers, b"")
+            .await
+            .map_err(AuthError::Signature)?;
+        Ok(Viewer::Actor(actor))
+    }
+
+    /// Whether this viewer may see `object`: everyone sees public objects,
+    /// actors also see what's addressed to them or to the followers
+    /// collection if they follow us.
+    pub fn can_see(&self, state: &AppState, object: &Object) -> Result<bool, StorageError> {
+        if object.is_public() {
+            return Ok(true);
+        }
+
+        match self {
+            Viewer::Owner => Ok(true),
+            Viewer::Anonymous => Ok(false),
+            Viewer::Actor(actor) => {
+                let followers = state.config.followers();
+                for recipient in object.recipients() {
+                    if recipient == actor
+                        || (recipient == &followers
+                            && state.storage.in_collection(&followers, actor)?)
+                    {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
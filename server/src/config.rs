@@ -0,0 +1,191 @@
+use std::{
+    error::Error,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Config {
+    #[serde(default = "Config::default_listen")]
+    pub listen: SocketAddr,
+
+    /// Where the server is reachable from the outside, like
+    /// `https://example.com`.
+    pub base_url: Url,
+
+    /// Where the delivery queue, the database and such are kept.
+    #[serde(default = "Config::default_data_dir")]
+    pub data_dir: PathBuf,
+
+    pub user: UserConfig,
+
+    #[serde(default)]
+    pub delivery: DeliveryConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UserConfig {
+    pub username: String,
+
+    /// Display name.
+    pub name: Option<String>,
+
+    /// HTML bio.
+    pub summary: Option<String>,
+
+    /// Avatar image.
+    pub icon: Option<Url>,
+
+    /// PEM encoded PKCS#8 or PKCS#1 private key, the public half of which is
+    /// published in the actor's `publicKey`.
+    pub private_key: PathBuf,
+
+    /// Whether follows have to be approved by posting an Accept to the
+    /// outbox, rather than being accepted right away.
+    #[serde(default)]
+    pub manually_approves_followers: bool,
+
+    /// Bearer token for posting to the outbox. Without one, client-to-server
+    /// posting is turned off.
+    pub token: Option<String>,
+
+    /// The rest default to paths under the actor ID, and `shared_inbox` to
+    /// `/inbox`.
+    pub inbox: Option<Url>,
+    pub outbox: Option<Url>,
+    pub followers: Option<Url>,
+    pub following: Option<Url>,
+    pub liked: Option<Url>,
+    pub shared_inbox: Option<Url>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+    /// How long to wait before the first retry. Each retry after that waits
+    /// twice as long as the one before.
+    pub retry_secs: u64,
+
+    /// The longest to wait between retries.
+    pub max_retry_secs: u64,
+
+    /// Hosts that have failed every delivery for this long are marked dead
+    /// and not delivered to until we hear from them again.
+    pub dead_after_secs: u64,
+
+    /// How long to wait for an inbox to answer.
+    pub timeout_secs: u64,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        DeliveryConfig {
+            retry_secs: 60,
+            max_retry_secs: 6 * 60 * 60,
+            dead_after_secs: 7 * 24 * 60 * 60,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// Where objects and collections are kept.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Kept in memory and lost on restart.
+    Memory,
+    /// An SQLite database, `smacktivity.db` in the data directory unless
+    /// `path` says otherwise.
+    Sqlite { path: Option<PathBuf> },
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::Sqlite { path: None }
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, Box<dyn Error>> {
+        let config = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&config)?)
+    }
+
+    fn default_listen() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 8080))
+    }
+
+    fn default_data_dir() -> PathBuf {
+        PathBuf::from("data")
+    }
+
+    /// The host part of `acct:` URIs.
+    pub fn domain(&self) -> String {
+        let host = self.base_url.host_str().unwrap_or_default();
+        match self.base_url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => String::from(host),
+        }
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        self.base_url.join(path).expect("base_url can be a base")
+    }
+
+    /// `acct:user@domain`
+    pub fn acct(&self) -> String {
+        format!("acct:{}@{}", self.user.username, self.domain())
+    }
+
+    pub fn actor_id(&self) -> Url {
+        self.url(&format!("/users/{}", self.user.username))
+    }
+
+    fn actor_url(&self, configured: &Option<Url>, collection: &str) -> Url {
+        configured
+            .clone()
+            .unwrap_or_else(|| self.url(&format!("/users/{}/{}", self.user.username, collection)))
+    }
+
+    pub fn inbox(&self) -> Url {
+        self.actor_url(&self.user.inbox, "inbox")
+    }
+
+    pub fn outbox(&self) -> Url {
+        self.actor_url(&self.user.outbox, "outbox")
+    }
+
+    pub fn followers(&self) -> Url {
+        self.actor_url(&self.user.followers, "followers")
+    }
+
+    pub fn following(&self) -> Url {
+        self.actor_url(&self.user.following, "following")
+    }
+
+    pub fn liked(&self) -> Url {
+        self.actor_url(&self.user.liked, "liked")
+    }
+
+    pub fn shared_inbox(&self) -> Url {
+        self.user
+            .shared_inbox
+            .clone()
+            .unwrap_or_else(|| self.url("/inbox"))
+    }
+
+    pub fn key_id(&self) -> Url {
+        let mut key_id = self.actor_id();
+        key_id.set_fragment(Some("main-key"));
+        key_id
+    }
+
+    /// The HTML profile page.
+    pub fn profile_url(&self) -> Url {
+        self.url(&format!("/@{}", self.user.username))
+    }
+}
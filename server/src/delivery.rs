@@ -0,0 +1,690 @@
+//! Sending activities to other servers.
+//!
+//! Activities are resolved to inboxes and put in a queue that's saved to
+//! disk, so deliveries that fail are retried with exponential backoff even
+//! across restarts. Hosts that keep failing for long enough are marked dead
+//! and skipped until we receive something from them again.
+
+use crate::{config::DeliveryConfig, storage::StorageError, AppState};
+use smacktivity::{ActivityStreamsType, LinkObject, NonFunctional, Object, PUBLIC};
+use smacktivity_network::{post_object, request_object_with, Gone};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Notify;
+use url::Url;
+
+/// Everyone `activity` is addressed to, with our followers collection
+/// expanded. Public and ourselves are skipped.
+pub fn recipients(state: &AppState, activity: &Object) -> Result<Vec<Url>, StorageError> {
+    let actor_id = state.config.actor_id();
+    let followers = state.config.followers();
+
+    let mut recipients = Vec::new();
+    for recipient in activity.recipients() {
+        if recipient == &followers {
+            recipients.extend(state.storage.collection(&followers)?);
+        } else if !matches!(recipient.as_str(), PUBLIC | "as:Public") && recipient != &actor_id {
+            recipients.push(recipient.clone());
+        }
+    }
+
+    recipients.sort();
+    recipients.dedup();
+    Ok(recipients)
+}
+
+/// Remove `bto` and `bcc` from an activity and its embedded object.
+pub fn strip_hidden_recipients(activity: &mut Object) {
+    activity.bto = NonFunctional::None;
+    activity.bcc = NonFunctional::None;
+    for object in activity.object.iter_mut() {
+        if let Some(object) = object.as_object_mut() {
+            object.bto = NonFunctional::None;
+            object.bcc = NonFunctional::None;
+        }
+    }
+}
+
+/// Queue `activity` for everyone it's addressed to. Recipients are resolved
+/// to inboxes in the background, so this returns right away.
+pub fn deliver(state: Arc<AppState>, activity: Object) {
+    let recipients = match recipients(&state, &activity) {
+        Ok(recipients) => recipients,
+        Err(err) => {
+            tracing::error!("couldn't find recipients: {}", err);
+            return;
+        }
+    };
+    let mut activity = activity;
+    strip_hidden_recipients(&mut activity);
+
+    tokio::spawn(async move {
+        for inbox in inboxes(&state, &recipients).await {
+            if let Err(err) = state.deliveries.enqueue(inbox, &activity) {
+                tracing::error!("couldn't queue delivery: {}", err);
+            }
+        }
+    });
+}
+
+/// The inboxes to deliver to for `recipients`, using shared inboxes where
+/// there are any so each server gets one copy.
+pub async fn inboxes(state: &AppState, recipients: &[Url]) -> BTreeSet<Url> {
+    let mut inboxes = BTreeSet::new();
+    for recipient in recipients {
+        if let Some(inbox) = state.deliveries.cached_inbox(recipient) {
+            inboxes.insert(inbox);
+            continue;
+        }
+        match inbox_of(state, recipient).await {
+            Ok(Some(inbox)) => {
+                state.deliveries.cache_inbox(recipient, &inbox);
+                inboxes.insert(inbox);
+            }
+            Ok(None) => tracing::debug!("{} has no inbox, not delivering", recipient),
+            Err(err) => tracing::warn!("couldn't find inbox for {}: {}", recipient, err),
+        }
+    }
+    inboxes
+}
+
+async fn inbox_of(state: &AppState, recipient: &Url) -> Result<Option<Url>, String> {
+    let actor = match request_object_with(recipient, Some(&state.signer)).await {
+        Ok(actor) => actor,
+        // deleted accounts don't get anything, and don't follow us anymore
+        Err(err) if err.is::<Gone>() => {
+            tracing::debug!("{} is gone, not delivering", recipient);
+            state
+                .storage
+                .remove(&state.config.followers(), recipient)
+                .map_err(|err| err.to_string())?;
+            return Ok(None);
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+    Ok(actor_inbox(&actor))
+}
+
+/// Where to deliver to `actor`, their shared inbox if they have one.
+pub fn actor_inbox(actor: &Object) -> Option<Url> {
+    // someone else's followers, or some other collection we can't expand
+    if actor.has_type(ActivityStreamsType::Collection)
+        || actor.has_type(ActivityStreamsType::OrderedCollection)
+    {
+        return None;
+    }
+
+    let shared_inbox = actor
+        .endpoints
+        .as_ref()
+        .and_then(|endpoints| endpoints.shared_inbox.clone());
+    shared_inbox.or_else(|| actor.inbox.as_ref().and_then(LinkObject::href).cloned())
+}
+
+/// One activity to one inbox.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub inbox: Url,
+    pub activity: Object,
+    /// How many times it's failed.
+    pub attempts: u32,
+    /// Unix time to try next.
+    pub next_attempt: u64,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+struct Host {
+    /// Unix time of the first failure since the last success.
+    failing_since: Option<u64>,
+    dead: bool,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Queue {
+    next_id: u64,
+    deliveries: Vec<Delivery>,
+    hosts: HashMap<String, Host>,
+
+    /// Deliveries being sent right now, which the worker shouldn't pick up
+    /// again.
+    #[serde(skip)]
+    in_flight: BTreeSet<u64>,
+}
+
+enum Outcome {
+    Delivered,
+    /// Try again later.
+    Failed(String),
+    /// The inbox refused it, so there's no point trying again.
+    Refused(String),
+}
+
+/// The outgoing deliveries, saved to a JSON file after every change.
+pub struct DeliveryQueue {
+    path: Option<PathBuf>,
+    config: DeliveryConfig,
+    queue: Mutex<Queue>,
+    inboxes: Mutex<HashMap<Url, Url>>,
+    notify: Notify,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl DeliveryQueue {
+    /// Load the queue from `path`, or start an empty one if it doesn't
+    /// exist yet.
+    pub fn open(path: impl AsRef<Path>, config: DeliveryConfig) -> io::Result<DeliveryQueue> {
+        let path = path.as_ref();
+        let queue = match std::fs::read(path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Queue::default(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(DeliveryQueue::new(Some(path.to_path_buf()), config, queue))
+    }
+
+    /// A queue that isn't saved anywhere.
+    pub fn in_memory(config: DeliveryConfig) -> DeliveryQueue {
+        DeliveryQueue::new(None, config, Queue::default())
+    }
+
+    fn new(path: Option<PathBuf>, config: DeliveryConfig, queue: Queue) -> DeliveryQueue {
+        DeliveryQueue {
+            path,
+            config,
+            queue: Mutex::new(queue),
+            inboxes: Mutex::new(HashMap::new()),
+            notify: Notify::new(),
+        }
+    }
+
+    fn save(&self, queue: &Queue) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        // write then rename so a crash doesn't leave half a file
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_vec(queue)?)?;
+        std::fs::rename(temp, path)
+    }
+
+    pub fn enqueue(&self, inbox: Url, activity: &Object) -> io::Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+
+        let host = inbox.host_str().unwrap_or_default();
+        if queue.hosts.get(host).is_some_and(|host| host.dead) {
+            tracing::debug!("not delivering to dead host {}", host);
+            return Ok(());
+        }
+
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.deliveries.push(Delivery {
+            id,
+            inbox,
+            activity: activity.clone(),
+            attempts: 0,
+            next_attempt: now(),
+        });
+
+        self.save(&queue)?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Everything still waiting to be delivered.
+    pub fn pending(&self) -> Vec<Delivery> {
+        self.queue.lock().unwrap().deliveries.clone()
+    }
+
+    pub fn dead_hosts(&self) -> Vec<String> {
+        let queue = self.queue.lock().unwrap();
+        queue
+            .hosts
+            .iter()
+            .filter(|(_, host)| host.dead)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// We heard from `host`, so it's alive again.
+    pub fn revive(&self, host: &str) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.hosts.remove(host).is_some_and(|host| host.dead) {
+            tracing::info!("{} is back", host);
+            if let Err(err) = self.save(&queue) {
+                tracing::error!("couldn't save delivery queue: {}", err);
+            }
+        }
+    }
+
+    fn cached_inbox(&self, actor: &Url) -> Option<Url> {
+        self.inboxes.lock().unwrap().get(actor).cloned()
+    }
+
+    pub fn cache_inbox(&self, actor: &Url, inbox: &Url) {
+        self.inboxes
+            .lock()
+            .unwrap()
+            .insert(actor.clone(), inbox.clone());
+    }
+
+    /// Deliveries that are due, marked as in flight. Also the unix time the
+    /// next one after those is due, if there is one.
+    fn take_due(&self) -> (Vec<Delivery>, Option<u64>) {
+        let mut queue = self.queue.lock().unwrap();
+        let now = now();
+
+        let mut due = Vec::new();
+        let mut next = None::<u64>;
+        for delivery in &queue.deliveries {
+            if queue.in_flight.contains(&delivery.id) {
+                continue;
+            }
+            if delivery.next_attempt <= now {
+                due.push(delivery.clone());
+            } else {
+                next = Some(next.map_or(delivery.next_attempt, |next| {
+                    next.min(delivery.next_attempt)
+                }));
+            }
+        }
+
+        queue
+            .in_flight
+            .extend(due.iter().map(|delivery| delivery.id));
+        (due, next)
+    }
+
+    fn backoff(&self, attempts: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        self.config
+            .retry_secs
+            .saturating_mul(factor)
+            .min(self.config.max_retry_secs)
+    }
+
+    fn finish(&self, delivery: &Delivery, outcome: Outcome) {
+        let mut queue = self.queue.lock().unwrap();
+        let now = now();
+        let host = String::from(delivery.inbox.host_str().unwrap_or_default());
+        queue.in_flight.remove(&delivery.id);
+
+        match outcome {
+            Outcome::Delivered => {
+                tracing::debug!("delivered {} to {}", delivery.id, delivery.inbox);
+                queue.hosts.remove(&host);
+                queue.deliveries.retain(|queued| queued.id != delivery.id);
+            }
+
+            Outcome::Refused(why) => {
+                tracing::warn!("{} refused delivery: {}", delivery.inbox, why);
+                queue.hosts.remove(&host);
+                queue.deliveries.retain(|queued| queued.id != delivery.id);
+            }
+
+            Outcome::Failed(why) => {
+                let dead_after = self.config.dead_after_secs;
+                let entry = queue.hosts.entry(host.clone()).or_default();
+                let failing_since = *entry.failing_since.get_or_insert(now);
+
+                if now.saturating_sub(failing_since) >= dead_after {
+                    tracing::warn!(
+                        "{} has been failing since {}, marking dead",
+                        host,
+                        failing_since
+                    );
+                    entry.dead = true;
+                    queue
+                        .deliveries
+                        .retain(|queued| queued.inbox.host_str().unwrap_or_default() != host);
+                } else if let Some(queued) = queue
+                    .deliveries
+                    .iter_mut()
+                    .find(|queued| queued.id == delivery.id)
+                {
+                    queued.attempts += 1;
+                    queued.next_attempt = now + self.backoff(queued.attempts);
+                    tracing::info!(
+                        "delivery to {} failed ({}), retry {} in {}s",
+                        delivery.inbox,
+                        why,
+                        queued.attempts,
+                        queued.next_attempt - now
+                    );
+                }
+            }
+        }
+
+        if let Err(err) = self.save(&queue) {
+            tracing::error!("couldn't save delivery queue: {}", err);
+        }
+        self.notify.notify_one();
+    }
+}
+
+/// Send queued deliveries as they come due, forever.
+pub async fn run(state: Arc<AppState>) {
+    loop {
+        let (due, next) = state.deliveries.take_due();
+        for delivery in due {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let outcome = send(&state, &delivery).await;
+                state.deliveries.finish(&delivery, outcome);
+            });
+        }
+
+        let wait = next
+            .map(|next| Duration::from_secs(next.saturating_sub(now()).max(1)))
+            .unwrap_or(Duration::from_secs(60 * 60));
+        tokio::select! {
+            _ = state.deliveries.notify.notified() => {}
+            _ = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+async fn send(state: &AppState, delivery: &Delivery) -> Outcome {
+    let timeout = Duration::from_secs(state.deliveries.config.timeout_secs);
+    let result = tokio::time::timeout(
+        timeout,
+        post_object(&delivery.inbox, &delivery.activity, Some(&state.signer)),
+    )
+    .await;
+
+    match result {
+        Ok(Ok(_)) => Outcome::Delivered,
+        Ok(Err(err)) => {
+            let status = err
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status);
+            match status {
+                // the inbox understood and said no, except for rate limits
+                // and timeouts
+                Some(status)
+                    if status.is_client_error()
+                        && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+                        && status != reqwest::StatusCode::REQUEST_TIMEOUT =>
+                {
+                    Outcome::Refused(err.to_string())
+                }
+                _ => Outcome::Failed(err.to_string()),
+            }
+        }
+        Err(_) => Outcome::Failed(String::from("timed out")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, url};
+    use axum::{
+        extract::Path as UrlPath,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    type Received = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// A server whose users all share one inbox, keeping what's posted to
+    /// any inbox.
+    fn elsewhere(received: Received) -> Url {
+        let router = Router::new()
+            .route(
+                "/users/:name",
+                get(
+                    |UrlPath(name): UrlPath<String>, headers: HeaderMap| async move {
+                        let host = headers["host"].to_str().unwrap();
+                        Json(json!({
+                            "type": "Person",
+                            "id": format!("http://{}/users/{}", host, name),
+                            "inbox": format!("http://{}/users/{}/inbox", host, name),
+                            "endpoints": {"sharedInbox": format!("http://{}/inbox", host)}
+                        }))
+                    },
+                ),
+            )
+            .route(
+                "/inbox",
+                post({
+                    let received = received.clone();
+                    |Json(body): Json<Value>| async move {
+                        received
+                            .lock()
+                            .unwrap()
+                            .push((String::from("/inbox"), body));
+                        StatusCode::ACCEPTED
+                    }
+                }),
+            )
+            .route(
+                "/users/:name/inbox",
+                post(
+                    |UrlPath(name): UrlPath<String>, Json(body): Json<Value>| async move {
+                        let inbox = format!("/users/{}/inbox", name);
+                        received.lock().unwrap().push((inbox, body));
+                        StatusCode::ACCEPTED
+                    },
+                ),
+            );
+        testing::serve(router)
+    }
+
+    /// A server that's always down.
+    fn unavailable() -> Url {
+        let router =
+            Router::new().route("/inbox", post(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        testing::serve(router).join("/inbox").unwrap()
+    }
+
+    fn create() -> Object {
+        serde_json::from_value(json!({
+            "type": "Create",
+            "id": "https://local.example/activities/1",
+            "actor": "https://local.example/users/alice",
+            "object": {"type": "Note", "content": "hi"}
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_once_per_server_without_hidden_recipients() {
+        let state = testing::state();
+        let received = Received::default();
+        let server = elsewhere(received.clone());
+        let user = |name: &str| server.join(&format!("/users/{}", name)).unwrap();
+
+        let activity = serde_json::from_value(json!({
+            "type": "Create",
+            "id": "https://local.example/activities/1",
+            "actor": "https://local.example/users/alice",
+            "to": [user("bob"), PUBLIC],
+            "cc": user("carol"),
+            "bto": user("dave"),
+            "bcc": user("erin"),
+            "object": {
+                "type": "Note",
+                "content": "hi",
+                "bcc": user("erin")
+            }
+        }))
+        .unwrap();
+        tokio::spawn(run(state.clone()));
+        deliver(state.clone(), activity);
+
+        for _ in 0..500 {
+            if !received.lock().unwrap().is_empty() && state.deliveries.pending().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (inbox, body) = &received[0];
+        assert_eq!(inbox, "/inbox");
+        assert_eq!(body["id"], "https://local.example/activities/1");
+        assert_eq!(body["cc"], user("carol").as_str());
+        assert!(body.get("bto").is_none());
+        assert!(body.get("bcc").is_none());
+        assert!(body["object"].get("bcc").is_none());
+    }
+
+    #[tokio::test]
+    async fn shared_inboxes_are_used_and_cached() {
+        let state = testing::state();
+        let server = elsewhere(Received::default());
+        let bob = server.join("/users/bob").unwrap();
+        let carol = server.join("/users/carol").unwrap();
+
+        let inboxes = inboxes(&state, &[bob.clone(), carol]).await;
+        assert_eq!(
+            inboxes.into_iter().collect::<Vec<_>>(),
+            [server.join("/inbox").unwrap()]
+        );
+        assert_eq!(
+            state.deliveries.cached_inbox(&bob),
+            Some(server.join("/inbox").unwrap())
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let state = testing::state_with(
+            "
+            [delivery]
+            retry_secs = 60
+            max_retry_secs = 300
+            ",
+        );
+        let backoff = |attempts| state.deliveries.backoff(attempts);
+        assert_eq!(backoff(1), 60);
+        assert_eq!(backoff(2), 120);
+        assert_eq!(backoff(3), 240);
+        assert_eq!(backoff(4), 300);
+        assert_eq!(backoff(100), 300);
+    }
+
+    #[tokio::test]
+    async fn failing_hosts_back_off_then_die() {
+        let data_dir =
+            std::env::temp_dir().join(format!("smacktivity-deliveries-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let open = |dead_after_secs: u64| {
+            testing::state_saving_deliveries(&format!(
+                r#"
+                data_dir = "{}"
+
+                [delivery]
+                retry_secs = 60
+                dead_after_secs = {}
+                "#,
+                data_dir.display(),
+                dead_after_secs
+            ))
+        };
+        let inbox = unavailable();
+
+        let state = open(60 * 60);
+        state.deliveries.enqueue(inbox.clone(), &create()).unwrap();
+        let (due, _) = state.deliveries.take_due();
+        assert_eq!(due.len(), 1);
+        let outcome = send(&state, &due[0]).await;
+        assert!(matches!(outcome, Outcome::Failed(_)));
+        state.deliveries.finish(&due[0], outcome);
+
+        let pending = state.deliveries.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        assert!((59..=60).contains(&(pending[0].next_attempt - now())));
+        let (due, next) = state.deliveries.take_due();
+        assert!(due.is_empty());
+        assert_eq!(next, Some(pending[0].next_attempt));
+        assert!(state.deliveries.dead_hosts().is_empty());
+
+        // after a restart the retry is still there, and since the host has
+        // been failing for longer than we now wait, it's marked dead
+        let state = open(0);
+        let pending = state.deliveries.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        let outcome = send(&state, &pending[0]).await;
+        state.deliveries.finish(&pending[0], outcome);
+        assert_eq!(state.deliveries.dead_hosts(), ["127.0.0.1"]);
+        assert!(state.deliveries.pending().is_empty());
+
+        let state = open(0);
+        assert_eq!(state.deliveries.dead_hosts(), ["127.0.0.1"]);
+        state.deliveries.enqueue(inbox.clone(), &create()).unwrap();
+        assert!(state.deliveries.pending().is_empty());
+
+        state.deliveries.revive("127.0.0.1");
+        state.deliveries.enqueue(inbox.clone(), &create()).unwrap();
+        let state = open(0);
+        assert!(state.deliveries.dead_hosts().is_empty());
+        assert_eq!(state.deliveries.pending().len(), 1);
+        assert_eq!(state.deliveries.pending()[0].attempts, 0);
+
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refused_deliveries_are_dropped() {
+        let router = Router::new().route("/inbox", post(|| async { StatusCode::BAD_REQUEST }));
+        let inbox = testing::serve(router).join("/inbox").unwrap();
+        let state = testing::state();
+
+        state.deliveries.enqueue(inbox, &create()).unwrap();
+        let (due, _) = state.deliveries.take_due();
+        let outcome = send(&state, &due[0]).await;
+        assert!(matches!(outcome, Outcome::Refused(_)));
+        state.deliveries.finish(&due[0], outcome);
+        assert!(state.deliveries.pending().is_empty());
+        assert!(state.deliveries.dead_hosts().is_empty());
+    }
+
+    #[test]
+    fn recipients_expand_followers() {
+        let state = testing::state();
+        let followers = state.config.followers();
+        state
+            .storage
+            .append(&followers, &url("https://remote.example/bob"))
+            .unwrap();
+
+        let activity = serde_json::from_value(json!({
+            "type": "Announce",
+            "to": [PUBLIC, followers],
+            "cc": ["https://local.example/users/alice", "https://remote.example/bob"],
+            "bcc": "https://other.example/carol"
+        }))
+        .unwrap();
+        assert_eq!(
+            recipients(&state, &activity).unwrap(),
+            [
+                url("https://other.example/carol"),
+                url("https://remote.example/bob")
+            ]
+        );
+    }
+}
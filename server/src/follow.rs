@@ -0,0 +1,568 @@
+//! Follow relationships in both directions.
+//!
+//! Someone following us sends a Follow, which is accepted right away, or
+//! kept in the follow requests collection until the owner posts an Accept or
+//! Reject for it if `manually_approves_followers` is set. Accepted followers
+//! are in the followers collection.
+//!
+//! When we follow someone, our Follow is kept in the pending follows
+//! collection until they Accept it, which puts them in the following
+//! collection, or Reject it.
+
+use crate::{
+    auth::Viewer,
+    config::Config,
+    outbox::{self, OutboxError, PageQuery},
+    storage::StorageError,
+    AppState,
+};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use smacktivity::{ActivityStreamsType, LinkObject, NonFunctional, Object};
+use std::sync::Arc;
+use url::Url;
+
+/// Follows waiting for the owner to approve them.
+pub fn follow_requests(config: &Config) -> Url {
+    config.url(&format!("/users/{}/follow_requests", config.user.username))
+}
+
+/// Follows we sent that haven't been accepted yet.
+pub fn pending_follows(config: &Config) -> Url {
+    config.url(&format!("/users/{}/pending_follows", config.user.username))
+}
+
+fn actor_of(follow: &Object) -> Option<&Url> {
+    follow.actor.iter().find_map(LinkObject::href)
+}
+
+fn followee_of(follow: &Object) -> Option<&Url> {
+    follow.object.iter().find_map(LinkObject::href)
+}
+
+/// The Follow an Accept, Reject or Undo is about, from storage or embedded.
+pub fn follow_of(state: &AppState, activity: &Object) -> Result<Option<Object>, StorageError> {
+    let object = match activity.object.iter().next() {
+        Some(object) => object,
+        None => return Ok(None),
+    };
+    let follow = match object.href() {
+        Some(id) => state.storage.get(id)?,
+        None => None,
+    };
+    Ok(follow
+        .or_else(|| object.as_object().cloned())
+        .filter(|follow| follow.has_type(ActivityStreamsType::Follow)))
+}
+
+/// The Follow we sent that an Accept or Reject from another server answers.
+/// Only our stored copy counts, whatever the activity embeds.
+pub fn sent_follow_of(state: &AppState, activity: &Object) -> Result<Option<Object>, StorageError> {
+    let id = match activity.object.iter().find_map(LinkObject::href) {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    Ok(state.storage.get(id)?.filter(|follow| {
+        follow.has_type(ActivityStreamsType::Follow)
+            && actor_of(follow) == Some(&state.config.actor_id())
+    }))
+}
+
+/// Fill in the Follow an Accept or Reject is about so the other server
+/// doesn't have to look it up, and address activities about follows to
+/// whoever's on the other end if the client didn't.
+pub fn prepare(state: &AppState, activity: &mut Object) -> Result<(), StorageError> {
+    let responding = activity.has_type(ActivityStreamsType::Accept)
+        || activity.has_type(ActivityStreamsType::Reject);
+    let undoing = activity.has_type(ActivityStreamsType::Undo);
+
+    let other_end = if activity.has_type(ActivityStreamsType::Follow) {
+        followee_of(activity).cloned()
+    } else if responding || undoing {
+        match follow_of(state, activity)? {
+            Some(follow) => {
+                let other_end = if responding {
+                    actor_of(&follow).cloned()
+                } else {
+                    followee_of(&follow).cloned()
+                };
+                if responding {
+                    activity.object = NonFunctional::One(LinkObject::Object(Box::new(follow)));
+                }
+                other_end
+            }
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    let addressed = activity.recipients().next().is_some();
+    if let (Some(other_end), false) = (other_end, addressed) {
+        activity.to = NonFunctional::One(LinkObject::Url(other_end));
+    }
+    Ok(())
+}
+
+/// Someone wants to follow us.
+pub fn requested(state: &Arc<AppState>, follow: &Object) -> Result<(), OutboxError> {
+    let (actor, id) = match (actor_of(follow), &follow.id) {
+        (Some(actor), Some(id)) => (actor, id),
+        _ => return Ok(()),
+    };
+
+    let following_already = state
+        .storage
+        .in_collection(&state.config.followers(), actor)?;
+    if state.config.user.manually_approves_followers && !following_already {
+        tracing::info!("{} asked to follow", actor);
+        state.storage.append(&follow_requests(&state.config), id)?;
+        return Ok(());
+    }
+
+    // answered again if they're already following, in case they missed it
+    let accept = Object {
+        type_: ActivityStreamsType::Accept.into(),
+        object: NonFunctional::One(LinkObject::Object(Box::new(follow.clone()))),
+        ..Default::default()
+    };
+    outbox::publish(state, accept).map(|_| ())
+}
+
+/// We accepted `follow`.
+pub fn approve(state: &AppState, follow: &Object) -> Result<(), StorageError> {
+    if let Some(actor) = actor_of(follow) {
+        tracing::info!("{} follows us", actor);
+        state.storage.append(&state.config.followers(), actor)?;
+    }
+    if let Some(id) = &follow.id {
+        state.storage.remove(&follow_requests(&state.config), id)?;
+    }
+    Ok(())
+}
+
+/// We rejected `follow`, or kicked out a follower.
+pub fn deny(state: &AppState, follow: &Object) -> Result<(), StorageError> {
+    if let Some(actor) = actor_of(follow) {
+        state.storage.remove(&state.config.followers(), actor)?;
+    }
+    if let Some(id) = &follow.id {
+        state.storage.remove(&follow_requests(&state.config), id)?;
+    }
+    Ok(())
+}
+
+/// `actor` undid their `follow`.
+pub fn unfollowed(state: &AppState, actor: &Url, follow: &Object) -> Result<(), StorageError> {
+    state.storage.remove(&state.config.followers(), actor)?;
+    if let Some(id) = &follow.id {
+        state.storage.remove(&follow_requests(&state.config), id)?;
+    }
+    Ok(())
+}
+
+/// We sent `follow`.
+pub fn sent(state: &AppState, follow: &Object) -> Result<(), StorageError> {
+    if let Some(id) = &follow.id {
+        state.storage.append(&pending_follows(&state.config), id)?;
+    }
+    Ok(())
+}
+
+/// `actor` accepted `follow`, if it's one of ours still waiting for them to.
+pub fn accepted(state: &AppState, actor: &Url, follow: &Object) -> Result<(), StorageError> {
+    let pending = pending_follows(&state.config);
+    let waiting = match &follow.id {
+        Some(id) => state.storage.in_collection(&pending, id)?,
+        None => false,
+    };
+    let ours = actor_of(follow) == Some(&state.config.actor_id());
+    if !waiting || !ours || followee_of(follow) != Some(actor) {
+        return Ok(());
+    }
+
+    tracing::info!("now following {}", actor);
+    if let Some(id) = &follow.id {
+        state.storage.remove(&pending, id)?;
+    }
+    state.storage.append(&state.config.following(), actor)
+}
+
+/// `actor` rejected `follow`, or stopped letting us follow them.
+pub fn rejected(state: &AppState, actor: &Url, follow: &Object) -> Result<(), StorageError> {
+    if followee_of(follow) != Some(actor) {
+        return Ok(());
+    }
+    if let Some(id) = &follow.id {
+        state.storage.remove(&pending_follows(&state.config), id)?;
+    }
+    state.storage.remove(&state.config.following(), actor)
+}
+
+/// We stopped following `followee`, or asking to.
+pub fn unfollowing(state: &AppState, followee: &Url) -> Result<(), StorageError> {
+    state.storage.remove(&state.config.following(), followee)?;
+
+    let pending = pending_follows(&state.config);
+    for id in state.storage.collection(&pending)? {
+        let follow = state.storage.get(&id)?;
+        if follow.as_ref().and_then(followee_of) == Some(followee) {
+            state.storage.remove(&pending, &id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Cut every follow relationship with `actor`, either way, after a Block.
+pub fn sever(state: &AppState, actor: &Url) -> Result<(), StorageError> {
+    unfollowing(state, actor)?;
+    state.storage.remove(&state.config.followers(), actor)?;
+
+    let requests = follow_requests(&state.config);
+    for id in state.storage.collection(&requests)? {
+        let follow = state.storage.get(&id)?;
+        if follow.as_ref().and_then(actor_of) == Some(actor) {
+            state.storage.remove(&requests, &id)?;
+        }
+    }
+    Ok(())
+}
+
+/// `actor`, who we follow, moved to `target`. Follow them there if `target`
+/// says it's also known as `actor`.
+pub fn moved(state: &Arc<AppState>, actor: &Url, target: &Url) {
+    match state
+        .storage
+        .in_collection(&state.config.following(), actor)
+    {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            tracing::error!("couldn't check if we follow {}: {}", actor, err);
+            return;
+        }
+    }
+
+    let (state, actor, target) = (state.clone(), actor.clone(), target.clone());
+    tokio::spawn(async move {
+        let moved =
+            match smacktivity_network::request_object_with(&target, Some(&state.signer)).await {
+                Ok(moved) => moved,
+                Err(err) => {
+                    tracing::warn!("couldn't fetch {}, who {} moved to: {}", target, actor, err);
+                    return;
+                }
+            };
+
+        let also_known_as = moved.rest.get("alsoKnownAs");
+        let confirmed = match also_known_as {
+            Some(serde_json::Value::String(alias)) => alias == actor.as_str(),
+            Some(serde_json::Value::Array(aliases)) => aliases
+                .iter()
+                .any(|alias| alias.as_str() == Some(actor.as_str())),
+            _ => false,
+        };
+        if !confirmed {
+            tracing::warn!("{} says it moved to {}, which doesn't agree", actor, target);
+            return;
+        }
+
+        tracing::info!("{} moved to {}, following them there", actor, target);
+        let follow = Object {
+            type_: ActivityStreamsType::Follow.into(),
+            object: NonFunctional::One(LinkObject::Url(target)),
+            ..Default::default()
+        };
+        let result = unfollowing(&state, &actor)
+            .map_err(OutboxError::from)
+            .and_then(|_| outbox::publish(&state, follow));
+        if let Err(err) = result {
+            tracing::error!("couldn't follow {}'s new account: {}", actor, err);
+        }
+    });
+}
+
+/// `GET /users/{username}/followers`
+pub async fn get_followers(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> Response {
+    if username != state.config.user.username {
+        return (StatusCode::NOT_FOUND, "no such user").into_response();
+    }
+    outbox::serve_collection(&state, &state.config.followers(), &query, outbox::ids)
+}
+
+/// `GET /users/{username}/following`
+pub async fn get_following(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> Response {
+    if username != state.config.user.username {
+        return (StatusCode::NOT_FOUND, "no such user").into_response();
+    }
+    outbox::serve_collection(&state, &state.config.following(), &query, outbox::ids)
+}
+
+/// `GET /users/{username}/follow_requests`, only for the owner, with the
+/// Follows themselves so their ids can be accepted or rejected.
+pub async fn get_follow_requests(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Query(query): Query<PageQuery>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Response {
+    if username != state.config.user.username {
+        return (StatusCode::NOT_FOUND, "no such user").into_response();
+    }
+    match Viewer::from_request(&state, &method, &uri, &headers).await {
+        Ok(Viewer::Owner) => {}
+        _ => return StatusCode::UNAUTHORIZED.into_response(),
+    }
+
+    let requests = follow_requests(&state.config);
+    let items = outbox::visible(&state, &Viewer::Owner);
+    outbox::serve_collection(&state, &requests, &query, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inbox::InboxError,
+        testing::{self, url},
+    };
+    use serde_json::json;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    fn follow_from(state: &AppState, actor: &str) -> Object {
+        let follow: Object = serde_json::from_value(json!({
+            "type": "Follow",
+            "id": format!("{}/follows/1", actor),
+            "actor": actor,
+            "object": state.config.actor_id()
+        }))
+        .unwrap();
+        state.storage.put(&follow).unwrap();
+        follow
+    }
+
+    fn answer(state: &AppState, type_: &str, follow: &Object) -> Object {
+        let answer = json!({ "type": type_, "object": follow.id });
+        let object = serde_json::from_value(answer.clone()).unwrap();
+        outbox::submit(state, object, &answer).unwrap()
+    }
+
+    fn followers(state: &AppState) -> Vec<Url> {
+        state.storage.collection(&state.config.followers()).unwrap()
+    }
+
+    fn requests(state: &AppState) -> Vec<Url> {
+        state
+            .storage
+            .collection(&follow_requests(&state.config))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn follows_are_accepted() {
+        let state = testing::state();
+        let bob = url("https://remote.example/bob");
+        state
+            .deliveries
+            .cache_inbox(&bob, &url("https://remote.example/bob/inbox"));
+
+        requested(&state, &follow_from(&state, bob.as_str())).unwrap();
+        assert_eq!(followers(&state), [bob]);
+        assert!(requests(&state).is_empty());
+    }
+
+    #[tokio::test]
+    async fn follows_can_wait_for_approval() {
+        let state = testing::state_with_user("manually_approves_followers = true");
+        let bob = follow_from(&state, "https://remote.example/bob");
+        let carol = follow_from(&state, "https://remote.example/carol");
+        requested(&state, &bob).unwrap();
+        requested(&state, &carol).unwrap();
+        assert!(followers(&state).is_empty());
+        assert_eq!(
+            requests(&state),
+            [bob.id.clone().unwrap(), carol.id.clone().unwrap()]
+        );
+
+        // the owner sees who's asking
+        let path = follow_requests(&state.config);
+        let list = |headers| {
+            let query = serde_json::from_value(json!({ "page": "true" })).unwrap();
+            get_follow_requests(
+                State(state.clone()),
+                Path(String::from("alice")),
+                Query(query),
+                Method::GET,
+                OriginalUri(path.path().parse().unwrap()),
+                headers,
+            )
+        };
+        assert_eq!(
+            list(HeaderMap::new()).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let page = testing::json(list(testing::owner()).await).await;
+        assert_eq!(
+            page["orderedItems"][0]["id"],
+            "https://remote.example/carol/follows/1"
+        );
+        assert_eq!(
+            page["orderedItems"][1]["id"],
+            "https://remote.example/bob/follows/1"
+        );
+
+        let accept = answer(&state, "Accept", &bob);
+        let embedded = accept
+            .object
+            .iter()
+            .find_map(LinkObject::as_object)
+            .unwrap();
+        assert_eq!(embedded.id, bob.id);
+        let to: Vec<&Url> = accept.to.iter().filter_map(LinkObject::href).collect();
+        assert_eq!(to, [&url("https://remote.example/bob")]);
+        answer(&state, "Reject", &carol);
+
+        assert_eq!(followers(&state), [url("https://remote.example/bob")]);
+        assert!(requests(&state).is_empty());
+    }
+
+    #[tokio::test]
+    async fn undone_follows_stop_following() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let follow = follow_from(&state, "https://remote.example/bob");
+        approve(&state, &follow).unwrap();
+
+        let undo = json!({
+            "type": "Undo",
+            "id": "https://remote.example/bob/undos/1",
+            "actor": "https://remote.example/bob",
+            "object": follow.id
+        });
+        testing::post_to_inbox(&state, &bob, undo).await.unwrap();
+        assert!(followers(&state).is_empty());
+
+        // and only the follower can
+        let follow = follow_from(&state, "https://remote.example/carol");
+        approve(&state, &follow).unwrap();
+        let undo = json!({
+            "type": "Undo",
+            "id": "https://remote.example/bob/undos/2",
+            "actor": "https://remote.example/bob",
+            "object": follow.id
+        });
+        assert!(matches!(
+            testing::post_to_inbox(&state, &bob, undo).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        assert_eq!(followers(&state), [url("https://remote.example/carol")]);
+    }
+
+    /// Where `https://remote.example/bob` moved to, which agrees that it's
+    /// also them if `agrees`. Counts how often it's fetched.
+    fn moved_to(agrees: bool, fetched: Arc<AtomicUsize>) -> Url {
+        let router = axum::Router::new().route(
+            "/bob",
+            axum::routing::get(move |headers: HeaderMap| async move {
+                fetched.fetch_add(1, Ordering::SeqCst);
+                let host = headers["host"].to_str().unwrap();
+                let also_known_as = if agrees {
+                    json!(["https://remote.example/bob"])
+                } else {
+                    json!([])
+                };
+                axum::Json(json!({
+                    "type": "Person",
+                    "id": format!("http://{}/bob", host),
+                    "inbox": format!("http://{}/bob/inbox", host),
+                    "alsoKnownAs": also_known_as
+                }))
+            }),
+        );
+        testing::serve(router).join("/bob").unwrap()
+    }
+
+    async fn wait_for(done: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if done() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn moves_are_followed() {
+        let state = testing::state();
+        let bob = url("https://remote.example/bob");
+        state
+            .storage
+            .append(&state.config.following(), &bob)
+            .unwrap();
+        let target = moved_to(true, Arc::default());
+
+        moved(&state, &bob, &target);
+        let pending = pending_follows(&state.config);
+        wait_for(|| state.storage.count(&pending).unwrap() > 0).await;
+
+        let follows = state.storage.collection(&pending).unwrap();
+        assert_eq!(follows.len(), 1);
+        let follow = state.storage.get(&follows[0]).unwrap().unwrap();
+        assert_eq!(followee_of(&follow), Some(&target));
+        assert!(state
+            .storage
+            .collection(&state.config.following())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn moves_need_the_target_to_agree() {
+        let state = testing::state();
+        let bob = url("https://remote.example/bob");
+        state
+            .storage
+            .append(&state.config.following(), &bob)
+            .unwrap();
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let target = moved_to(false, fetched.clone());
+
+        moved(&state, &bob, &target);
+        wait_for(|| fetched.load(Ordering::SeqCst) > 0).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(
+            state.storage.collection(&state.config.following()).unwrap(),
+            [bob]
+        );
+        assert_eq!(
+            state
+                .storage
+                .count(&pending_follows(&state.config))
+                .unwrap(),
+            0
+        );
+
+        // and nobody we don't follow gets followed elsewhere
+        let carol = url("https://remote.example/carol");
+        moved(&state, &carol, &target);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(fetched.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,992 @@
+//! Receiving activities from other servers.
+
+use crate::{delivery, follow, outbox::OutboxError, storage::StorageError, AppState};
+use axum::{
+    body::Bytes,
+    extract::{OriginalUri, Path, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use smacktivity::{jsonld, ActivityStreamsType, DateTime, LinkObject, Object};
+use smacktivity_network::{request_object_with, verify::check_fetched, Gone, VerifyError};
+use std::sync::Arc;
+use url::Url;
+
+#[derive(Debug)]
+pub enum InboxError {
+    Verify(VerifyError),
+    /// The body isn't an ActivityStreams object.
+    Json(serde_json::Error),
+    /// Activities without an `id` can't be deduplicated or stored.
+    MissingId,
+    /// The activity's `actor` isn't whoever signed the request.
+    ActorMismatch {
+        signer: Url,
+    },
+    /// The actor tried to act on something that isn't theirs.
+    Forbidden(String),
+    /// An object the activity refers to couldn't be fetched.
+    Fetch(String),
+    Storage(StorageError),
+    /// Answering the activity, like accepting a Follow, failed.
+    Respond(OutboxError),
+}
+
+impl std::fmt::Display for InboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InboxError::Verify(err) => write!(f, "couldn't verify signature: {}", err),
+            InboxError::Json(err) => write!(f, "invalid activity: {}", err),
+            InboxError::MissingId => write!(f, "activity has no id"),
+            InboxError::ActorMismatch { signer } => {
+                write!(f, "activity's actor isn't the signer {}", signer)
+            }
+            InboxError::Forbidden(why) => write!(f, "forbidden: {}", why),
+            InboxError::Fetch(err) => write!(f, "couldn't fetch object: {}", err),
+            InboxError::Storage(err) => write!(f, "{}", err),
+            InboxError::Respond(err) => write!(f, "couldn't respond: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for InboxError {}
+
+impl From<StorageError> for InboxError {
+    fn from(err: StorageError) -> Self {
+        InboxError::Storage(err)
+    }
+}
+
+impl IntoResponse for InboxError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            InboxError::Verify(_) => StatusCode::UNAUTHORIZED,
+            InboxError::Json(_) | InboxError::MissingId => StatusCode::BAD_REQUEST,
+            InboxError::ActorMismatch { .. } | InboxError::Forbidden(_) => StatusCode::FORBIDDEN,
+            InboxError::Fetch(_) => StatusCode::BAD_GATEWAY,
+            InboxError::Storage(_) | InboxError::Respond(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        tracing::debug!("inbox: {}", self);
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// `POST /inbox`
+pub async fn shared_inbox(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, InboxError> {
+    receive(&state, &method, &uri, &headers, &body).await
+}
+
+/// `POST /users/{username}/inbox`
+pub async fn user_inbox(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, InboxError> {
+    if username != state.config.user.username {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+    receive(&state, &method, &uri, &headers, &body).await
+}
+
+/// Verify and handle an activity POSTed to one of the inboxes.
+pub async fn receive(
+    state: &Arc<AppState>,
+    method: &Method,
+    uri: &axum::http::Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<StatusCode, InboxError> {
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let signer = state
+        .verifier
+        .verify(method.as_str(), path, headers, body)
+        .await
+        .map_err(InboxError::Verify)?;
+    if let Some(host) = signer.host_str() {
+        state.deliveries.revive(host);
+    }
+
+    let activity = jsonld::parse_object(body).map_err(InboxError::Json)?;
+    let id = activity.id.clone().ok_or(InboxError::MissingId)?;
+    // only the actor's own server gets to say what its activities are
+    if id.origin() != signer.origin() {
+        return Err(InboxError::Forbidden(format!(
+            "{} isn't on {}'s server",
+            id, signer
+        )));
+    }
+
+    let mut actors = activity
+        .actor
+        .iter()
+        .filter_map(LinkObject::href)
+        .peekable();
+    if actors.peek().is_none() || actors.any(|actor| actor != &signer) {
+        return Err(InboxError::ActorMismatch { signer });
+    }
+
+    if state.storage.contains(&id)? {
+        tracing::debug!("already have {}", id);
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    tracing::info!("{} from {}", activity.type_.primary().as_str(), signer);
+    handle(state, &signer, &activity).await?;
+
+    state.storage.put(&activity)?;
+    state.storage.append(&state.config.inbox(), &id)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Apply an activity's side effects, after it's been verified as coming
+/// from `actor` but before it's stored.
+pub async fn handle(
+    state: &Arc<AppState>,
+    actor: &Url,
+    activity: &Object,
+) -> Result<(), InboxError> {
+    match activity.type_.primary() {
+        ActivityStreamsType::Follow => follow(state, actor, activity),
+        ActivityStreamsType::Undo => undo(state, actor, activity),
+        ActivityStreamsType::Create => create(state, actor, activity).await,
+        ActivityStreamsType::Update => update(state, actor, activity),
+        ActivityStreamsType::Delete => delete(state, actor, activity),
+        ActivityStreamsType::Like => react(state, activity, "likes"),
+        ActivityStreamsType::Announce => react(state, activity, "shares"),
+        ActivityStreamsType::Accept => accept(state, actor, activity),
+        ActivityStreamsType::Reject => reject(state, actor, activity),
+        ActivityStreamsType::Block => block(state, actor, activity),
+        ActivityStreamsType::Move => move_(state, actor, activity),
+        _ => Ok(()),
+    }
+}
+
+/// The object of an activity, embedded or from storage.
+fn object_of(state: &AppState, activity: &Object) -> Result<Option<Object>, InboxError> {
+    let object = match activity.object.iter().next() {
+        Some(object) => object,
+        None => return Ok(None),
+    };
+    match object.href() {
+        Some(id) => match state.storage.get(id)? {
+            Some(stored) => Ok(Some(stored)),
+            None => Ok(object.as_object().cloned()),
+        },
+        None => Ok(object.as_object().cloned()),
+    }
+}
+
+fn object_id(activity: &Object) -> Option<&Url> {
+    activity.object.iter().find_map(LinkObject::href)
+}
+
+/// Whether `actor` is `object`, made it, or performed it.
+pub fn owned_by(object: &Object, actor: &Url) -> bool {
+    object.id.as_ref() == Some(actor)
+        || object
+            .attributed_to
+            .iter()
+            .chain(object.actor.iter())
+            .filter_map(LinkObject::href)
+            .any(|owner| owner == actor)
+}
+
+/// The collection of likes or shares of one of our objects.
+pub fn object_collection(object: &Url, name: &str) -> Url {
+    Url::parse(&format!(
+        "{}/{}",
+        object.as_str().trim_end_matches('/'),
+        name
+    ))
+    .expect("appending a path segment is still a URL")
+}
+
+fn is_local(state: &AppState, id: &Url) -> bool {
+    id.origin() == state.config.base_url.origin()
+}
+
+fn follow(state: &Arc<AppState>, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    if object_id(activity) != Some(&state.config.actor_id()) {
+        return Ok(());
+    }
+    if !owned_by(activity, actor) {
+        return Err(InboxError::Forbidden(String::from(
+            "can't follow on someone else's behalf",
+        )));
+    }
+    follow::requested(state, activity).map_err(InboxError::Respond)
+}
+
+fn undo(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    let undone = match object_of(state, activity)? {
+        Some(undone) => undone,
+        None => return Ok(()),
+    };
+    if !owned_by(&undone, actor) {
+        return Err(InboxError::Forbidden(String::from(
+            "can't undo someone else's activity",
+        )));
+    }
+
+    match undone.type_.primary() {
+        ActivityStreamsType::Follow => follow::unfollowed(state, actor, &undone)?,
+        ActivityStreamsType::Like | ActivityStreamsType::Announce => {
+            let name = if undone.has_type(ActivityStreamsType::Like) {
+                "likes"
+            } else {
+                "shares"
+            };
+            if let (Some(object), Some(id)) = (object_id(&undone), undone.id.as_ref()) {
+                state.storage.remove(&object_collection(object, name), id)?;
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(id) = &undone.id {
+        state.storage.tombstone(id)?;
+    }
+    Ok(())
+}
+
+async fn create(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    let created = match activity.object.iter().next() {
+        Some(created) => created,
+        None => return Ok(()),
+    };
+    let id = match created.as_object() {
+        Some(object) => object.id.as_ref(),
+        None => created.href(),
+    };
+    let id = match id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    if is_local(state, id) {
+        return Err(InboxError::Forbidden(format!("{} already exists", id)));
+    }
+    // redeliveries, or something we already fetched as a reply's parent
+    match state.storage.get(id)? {
+        Some(stored) if owned_by(&stored, actor) => return Ok(()),
+        Some(_) => return Err(InboxError::Forbidden(format!("{} already exists", id))),
+        None => {}
+    }
+
+    // the actor's server vouches for what's on it, anything else is fetched
+    // from where it lives
+    let object = match created.as_object() {
+        Some(object) if id.origin() == actor.origin() => object.clone(),
+        _ => match fetch(state, id).await? {
+            Some(object) => object,
+            None => return Ok(()),
+        },
+    };
+
+    if !object
+        .attributed_to
+        .iter()
+        .filter_map(LinkObject::href)
+        .any(|owner| owner == actor)
+    {
+        return Err(InboxError::Forbidden(format!(
+            "{} isn't attributed to {}",
+            id, actor
+        )));
+    }
+
+    state.storage.put(&object)?;
+    Ok(())
+}
+
+/// Fetch a remote object, or `None` if it's been deleted.
+async fn fetch(state: &AppState, id: &Url) -> Result<Option<Object>, InboxError> {
+    let object = match request_object_with(id, Some(&state.signer)).await {
+        Ok(object) => object,
+        Err(err) if err.is::<Gone>() => return Ok(None),
+        Err(err) => return Err(InboxError::Fetch(err.to_string())),
+    };
+    check_fetched(id, &object).map_err(|err| InboxError::Forbidden(err.to_string()))?;
+    Ok(Some(object))
+}
+
+// updates replace the whole object, and the version they replace is kept
+// as a revision
+fn update(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    let object = match activity
+        .object
+        .iter()
+        .next()
+        .and_then(LinkObject::as_object)
+    {
+        Some(object) => object,
+        None => return Ok(()),
+    };
+    let id = match &object.id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    if !object.attributed_to.is_none() && !owned_by(object, actor) {
+        return Err(InboxError::Forbidden(String::from(
+            "updated object is attributed to someone else",
+        )));
+    }
+
+    let mut updated = object.clone();
+    let mut previous = None;
+    match state.storage.get(id)? {
+        // deleted stays deleted
+        Some(stored) if stored.has_type(ActivityStreamsType::Tombstone) => return Ok(()),
+        Some(stored) if owned_by(&stored, actor) => {
+            previous = stored.updated.clone();
+            if updated.attributed_to.is_none() {
+                updated.attributed_to = stored.attributed_to;
+            }
+            if updated.published.is_none() {
+                updated.published = stored.published;
+            }
+        }
+        Some(_) => {
+            return Err(InboxError::Forbidden(format!(
+                "{} doesn't belong to {}",
+                id, actor
+            )))
+        }
+        // worth keeping when it's the actor's own profile
+        None if id == actor => {}
+        // we don't know about it, so there's nothing to update
+        None => return Ok(()),
+    }
+    if updated.updated.is_none() {
+        updated.updated = Some(activity.published.clone().unwrap_or_else(DateTime::now));
+    }
+    // a late or replayed Update mustn't roll back a newer version
+    let when = |updated: &Option<DateTime>| updated.as_ref().and_then(DateTime::to_utc);
+    if let (Some(new), Some(old)) = (when(&updated.updated), when(&previous)) {
+        if new <= old {
+            tracing::info!("ignoring stale update of {} from {}", id, actor);
+            return Ok(());
+        }
+    }
+
+    if id == actor {
+        if let Some(inbox) = delivery::actor_inbox(&updated) {
+            state.deliveries.cache_inbox(actor, &inbox);
+        }
+    }
+    tracing::info!("{} updated {}", actor, id);
+    state.storage.revise(&updated)?;
+    Ok(())
+}
+
+fn delete(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    let id = match object_id(activity) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    match state.storage.get(id)? {
+        Some(stored) if stored.has_type(ActivityStreamsType::Tombstone) => Ok(()),
+        Some(stored) if owned_by(&stored, actor) => {
+            tracing::info!("{} deleted {}", actor, id);
+            state.storage.tombstone(id)?;
+            Ok(())
+        }
+        Some(_) => Err(InboxError::Forbidden(format!(
+            "{} doesn't belong to {}",
+            id, actor
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn react(state: &AppState, activity: &Object, name: &str) -> Result<(), InboxError> {
+    match (object_id(activity), &activity.id) {
+        (Some(object), Some(id)) if is_local(state, object) => {
+            state.storage.append(&object_collection(object, name), id)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// the object is the Follow we sent
+fn accept(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    if let Some(sent) = follow::sent_follow_of(state, activity)? {
+        follow::accepted(state, actor, &sent)?;
+    }
+    Ok(())
+}
+
+fn reject(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    if let Some(sent) = follow::sent_follow_of(state, activity)? {
+        follow::rejected(state, actor, &sent)?;
+    }
+    Ok(())
+}
+
+// blocks of anyone else are none of our business
+fn block(state: &AppState, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    if object_id(activity) == Some(&state.config.actor_id()) {
+        follow::sever(state, actor)?;
+    }
+    Ok(())
+}
+
+// moving to `target`, so follow them there if we followed them here
+fn move_(state: &Arc<AppState>, actor: &Url, activity: &Object) -> Result<(), InboxError> {
+    if object_id(activity) != Some(actor) {
+        return Err(InboxError::Forbidden(String::from(
+            "can only move your own account",
+        )));
+    }
+    if let Some(target) = activity.target.iter().find_map(LinkObject::href) {
+        follow::moved(state, actor, target);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, post_to_inbox as post, url};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn stores_activities() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let like = json!({
+            "type": "Like",
+            "id": "https://remote.example/likes/1",
+            "actor": "https://remote.example/bob",
+            "object": "https://local.example/users/alice/statuses/1"
+        });
+
+        assert_eq!(
+            post(&state, &bob, like).await.unwrap(),
+            StatusCode::ACCEPTED
+        );
+        assert!(state
+            .storage
+            .contains(&url("https://remote.example/likes/1"))
+            .unwrap());
+    }
+
+    fn follow_each_other(state: &AppState, actor: &str) {
+        state
+            .storage
+            .append(&state.config.followers(), &url(actor))
+            .unwrap();
+        state
+            .storage
+            .append(&state.config.following(), &url(actor))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn blocks_sever_follows() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        follow_each_other(&state, "https://remote.example/bob");
+        follow_each_other(&state, "https://remote.example/carol");
+
+        let block = json!({
+            "type": "Block",
+            "id": "https://remote.example/blocks/1",
+            "actor": "https://remote.example/bob",
+            "object": "https://remote.example/carol"
+        });
+        post(&state, &bob, block).await.unwrap();
+        assert_eq!(state.storage.count(&state.config.followers()).unwrap(), 2);
+        assert_eq!(state.storage.count(&state.config.following()).unwrap(), 2);
+
+        let block = json!({
+            "type": "Block",
+            "id": "https://remote.example/blocks/2",
+            "actor": "https://remote.example/bob",
+            "object": "https://local.example/users/alice"
+        });
+        post(&state, &bob, block).await.unwrap();
+        assert_eq!(
+            state.storage.collection(&state.config.followers()).unwrap(),
+            [url("https://remote.example/carol")]
+        );
+        assert_eq!(
+            state.storage.collection(&state.config.following()).unwrap(),
+            [url("https://remote.example/carol")]
+        );
+    }
+
+    fn create(actor: &str, object: serde_json::Value) -> serde_json::Value {
+        json!({
+            "type": "Create",
+            "id": format!("{}/activities/1", actor),
+            "actor": actor,
+            "object": object
+        })
+    }
+
+    #[tokio::test]
+    async fn keeps_objects_from_the_actors_server() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": "hello"
+        });
+
+        post(&state, &bob, create("https://remote.example/bob", note))
+            .await
+            .unwrap();
+        let stored = state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn created_objects_need_an_author() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "content": "hello"
+        });
+
+        assert!(matches!(
+            post(&state, &bob, create("https://remote.example/bob", note)).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        assert!(!state
+            .storage
+            .contains(&url("https://remote.example/notes/1"))
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn accepts_creates_of_objects_we_already_have() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": "hello"
+        });
+        // fetched earlier, say as the parent of a reply
+        state
+            .storage
+            .put(&serde_json::from_value(note.clone()).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            post(&state, &bob, create("https://remote.example/bob", note))
+                .await
+                .unwrap(),
+            StatusCode::ACCEPTED
+        );
+        assert!(state
+            .storage
+            .contains(&url("https://remote.example/bob/activities/1"))
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn rejects_creates_of_someone_elses_objects() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/carol",
+            "content": "hello"
+        });
+        state
+            .storage
+            .put(&serde_json::from_value(note).unwrap())
+            .unwrap();
+
+        let forged = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": "mine now"
+        });
+        assert!(matches!(
+            post(&state, &bob, create("https://remote.example/bob", forged)).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        let stored = state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello");
+    }
+
+    fn update(actor: &str, object: serde_json::Value) -> serde_json::Value {
+        json!({
+            "type": "Update",
+            "id": format!("{}/activities/{}", actor, object["updated"].as_str().unwrap()),
+            "actor": actor,
+            "object": object
+        })
+    }
+
+    fn edit(content: &str, updated: &str) -> serde_json::Value {
+        json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": content,
+            "published": "2024-01-01T00:00:00Z",
+            "updated": updated
+        })
+    }
+
+    fn stored_note(state: &AppState) -> Object {
+        state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn updates_keep_history() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let mut note = edit("hello", "2024-01-01T00:00:00Z");
+        note.as_object_mut().unwrap().remove("updated");
+        post(&state, &bob, create("https://remote.example/bob", note))
+            .await
+            .unwrap();
+
+        for (content, updated) in [
+            ("hello, edited", "2024-01-02T00:00:00Z"),
+            // Mastodon sends edits that only change `updated`
+            ("hello, edited", "2024-01-03T00:00:00Z"),
+        ] {
+            let update = update("https://remote.example/bob", edit(content, updated));
+            assert_eq!(
+                post(&state, &bob, update).await.unwrap(),
+                StatusCode::ACCEPTED
+            );
+        }
+
+        let stored = stored_note(&state);
+        assert_eq!(stored.content.iter().next().unwrap(), "hello, edited");
+        assert_eq!(stored.updated.unwrap().as_str(), "2024-01-03T00:00:00Z");
+        let revisions = state
+            .storage
+            .revisions(&url("https://remote.example/notes/1"))
+            .unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].content.iter().next().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn stale_updates_are_ignored() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = edit("hello, edited twice", "2024-01-03T00:00:00Z");
+        state
+            .storage
+            .put(&serde_json::from_value(note).unwrap())
+            .unwrap();
+
+        for updated in ["2024-01-02T00:00:00Z", "2024-01-03T00:00:00Z"] {
+            let update = update(
+                "https://remote.example/bob",
+                edit("hello, edited once", updated),
+            );
+            assert_eq!(
+                post(&state, &bob, update).await.unwrap(),
+                StatusCode::ACCEPTED
+            );
+        }
+
+        let stored = stored_note(&state);
+        assert_eq!(stored.content.iter().next().unwrap(), "hello, edited twice");
+        assert!(state
+            .storage
+            .revisions(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_owners_update() {
+        let state = testing::state();
+        let carol = testing::remote(&state, "https://remote.example/carol");
+        let note = edit("hello", "2024-01-01T00:00:00Z");
+        state
+            .storage
+            .put(&serde_json::from_value(note).unwrap())
+            .unwrap();
+
+        // claiming to be bob's
+        let forged = update(
+            "https://remote.example/carol",
+            edit("carol was here", "2024-01-02T00:00:00Z"),
+        );
+        // or not saying whose it is
+        let mut unattributed = edit("carol was here", "2024-01-03T00:00:00Z");
+        unattributed.as_object_mut().unwrap().remove("attributedTo");
+        let unattributed = update("https://remote.example/carol", unattributed);
+
+        for update in [forged, unattributed] {
+            let err = post(&state, &carol, update).await.unwrap_err();
+            assert!(matches!(err, InboxError::Forbidden(_)));
+            assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+        }
+        assert_eq!(stored_note(&state).content.iter().next().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn deletes_leave_tombstones() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let carol = testing::remote(&state, "https://remote.example/carol");
+        let note = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": "hello"
+        });
+        state
+            .storage
+            .put(&serde_json::from_value(note).unwrap())
+            .unwrap();
+        let delete = |actor: &str| {
+            json!({
+                "type": "Delete",
+                "id": format!("{}/activities/2", actor),
+                "actor": actor,
+                "object": "https://remote.example/notes/1"
+            })
+        };
+
+        assert!(matches!(
+            post(&state, &carol, delete("https://remote.example/carol")).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        let stored = state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert!(stored.has_type(ActivityStreamsType::Note));
+
+        assert_eq!(
+            post(&state, &bob, delete("https://remote.example/bob"))
+                .await
+                .unwrap(),
+            StatusCode::ACCEPTED
+        );
+        let stored = state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert!(stored.has_type(ActivityStreamsType::Tombstone));
+        assert!(stored.content.iter().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn understands_renamed_terms() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let create = json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                {"body": "as:content", "author": {"@id": "as:attributedTo", "@type": "@id"}}
+            ],
+            "type": "Create",
+            "id": "https://remote.example/bob/activities/1",
+            "actor": "https://remote.example/bob",
+            "object": {
+                "type": "Note",
+                "id": "https://remote.example/notes/1",
+                "author": "https://remote.example/bob",
+                "body": "hello"
+            }
+        });
+
+        post(&state, &bob, create).await.unwrap();
+        let stored = state
+            .storage
+            .get(&url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello");
+    }
+
+    /// A server with one note by `author` on it, which calls `content`
+    /// something else.
+    fn elsewhere(author: &'static str) -> Url {
+        let router = axum::Router::new().route(
+            "/notes/1",
+            axum::routing::get(move |headers: HeaderMap| async move {
+                let host = headers["host"].to_str().unwrap();
+                axum::Json(json!({
+                    "@context": ["https://www.w3.org/ns/activitystreams", {"body": "as:content"}],
+                    "type": "Note",
+                    "id": format!("http://{}/notes/1", host),
+                    "attributedTo": author,
+                    "body": "fetched"
+                }))
+            }),
+        );
+        testing::serve(router).join("/notes/1").unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetches_objects_from_other_servers() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let id = elsewhere("https://remote.example/bob");
+        let note = json!({
+            "type": "Note",
+            "id": id,
+            "attributedTo": "https://remote.example/bob",
+            "content": "forged"
+        });
+
+        post(&state, &bob, create("https://remote.example/bob", note))
+            .await
+            .unwrap();
+        let stored = state.storage.get(&id).unwrap().unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "fetched");
+    }
+
+    #[tokio::test]
+    async fn rejects_other_servers_objects_by_someone_else() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let id = elsewhere("https://remote.example/carol");
+        let note = json!({
+            "type": "Note",
+            "id": id,
+            "attributedTo": "https://remote.example/bob",
+            "content": "forged"
+        });
+
+        assert!(matches!(
+            post(&state, &bob, create("https://remote.example/bob", note)).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        assert!(!state.storage.contains(&id).unwrap());
+    }
+
+    /// Store a Follow of `followee` we sent, waiting to be accepted.
+    fn sent_follow(state: &AppState, followee: &str) -> Url {
+        let follow: Object = serde_json::from_value(json!({
+            "type": "Follow",
+            "id": "https://local.example/users/alice/activities/1",
+            "actor": "https://local.example/users/alice",
+            "object": followee
+        }))
+        .unwrap();
+        state.storage.put(&follow).unwrap();
+        follow::sent(state, &follow).unwrap();
+        follow.id.unwrap()
+    }
+
+    fn accept(follow: serde_json::Value) -> serde_json::Value {
+        json!({
+            "type": "Accept",
+            "id": "https://remote.example/accepts/1",
+            "actor": "https://remote.example/bob",
+            "object": follow
+        })
+    }
+
+    #[tokio::test]
+    async fn accepts_follows_we_sent() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let follow = sent_follow(&state, "https://remote.example/bob");
+
+        post(&state, &bob, accept(json!(follow))).await.unwrap();
+        assert_eq!(
+            state.storage.collection(&state.config.following()).unwrap(),
+            [url("https://remote.example/bob")]
+        );
+        assert_eq!(
+            state
+                .storage
+                .count(&follow::pending_follows(&state.config))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_embedded_follows() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let forged = json!({
+            "type": "Follow",
+            "id": "https://local.example/users/alice/activities/2",
+            "actor": "https://local.example/users/alice",
+            "object": "https://remote.example/bob"
+        });
+
+        post(&state, &bob, accept(forged)).await.unwrap();
+        assert_eq!(state.storage.count(&state.config.following()).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn ignores_follows_that_arent_pending() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let follow = sent_follow(&state, "https://remote.example/bob");
+        state
+            .storage
+            .remove(&follow::pending_follows(&state.config), &follow)
+            .unwrap();
+
+        post(&state, &bob, accept(json!(follow))).await.unwrap();
+        assert_eq!(state.storage.count(&state.config.following()).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_ids_on_other_servers() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let like = json!({
+            "type": "Like",
+            "id": "https://elsewhere.example/likes/1",
+            "actor": "https://remote.example/bob",
+            "object": "https://local.example/users/alice/statuses/1"
+        });
+
+        assert!(matches!(
+            post(&state, &bob, like).await,
+            Err(InboxError::Forbidden(_))
+        ));
+        assert!(!state
+            .storage
+            .contains(&url("https://elsewhere.example/likes/1"))
+            .unwrap());
+    }
+}
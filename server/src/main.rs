@@ -1,3 +1,109 @@
-fn main() {
-    println!("Hello, world!");
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+
+mod actor;
+mod auth;
+mod config;
+mod delivery;
+mod follow;
+mod inbox;
+mod negotiate;
+mod objects;
+mod outbox;
+mod storage;
+#[cfg(test)]
+mod testing;
+mod webfinger;
+
+use config::Config;
+use delivery::DeliveryQueue;
+use smacktivity_network::{Signer, Verifier};
+use storage::Storage;
+
+#[derive(argh::FromArgs)]
+#[argh(description = "single-user activitypub server")]
+struct Args {
+    #[argh(
+        option,
+        default = "String::from(\"smacktivity.toml\")",
+        description = "config file"
+    )]
+    config: String,
+}
+
+pub struct AppState {
+    pub config: Config,
+    pub signer: Signer,
+    pub verifier: Verifier,
+    pub storage: Box<dyn Storage>,
+    pub deliveries: DeliveryQueue,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/.well-known/webfinger", get(webfinger::webfinger))
+        .route("/inbox", post(inbox::shared_inbox))
+        .route("/api/revisions", get(objects::get_revisions))
+        .route("/users/:username", get(actor::get_actor))
+        .route("/users/:username/inbox", post(inbox::user_inbox))
+        .route(
+            "/users/:username/outbox",
+            get(outbox::get_outbox).post(outbox::post_outbox),
+        )
+        .route("/users/:username/followers", get(follow::get_followers))
+        .route("/users/:username/following", get(follow::get_following))
+        .route(
+            "/users/:username/follow_requests",
+            get(follow::get_follow_requests),
+        )
+        .route("/users/:username/statuses/:id", get(objects::get_object))
+        .route("/users/:username/activities/:id", get(objects::get_object))
+        .route("/:profile", get(actor::get_profile))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let args: Args = argh::from_env();
+    let config = Config::load(&args.config)?;
+    let listen = config.listen;
+
+    let pem = std::fs::read_to_string(&config.user.private_key)?;
+    let signer = Signer::from_pem(config.key_id(), &pem)?;
+    let verifier = Verifier::new()
+        .with_scheme(config.base_url.scheme())
+        .with_signer(signer.clone());
+
+    std::fs::create_dir_all(&config.data_dir)?;
+    let deliveries = DeliveryQueue::open(
+        config.data_dir.join("deliveries.json"),
+        config.delivery.clone(),
+    )?;
+    let storage = storage::open(&config)?;
+    tracing::info!(
+        "{} deliveries pending, dead hosts: {:?}",
+        deliveries.pending().len(),
+        deliveries.dead_hosts()
+    );
+
+    let state = Arc::new(AppState {
+        config,
+        signer,
+        verifier,
+        storage,
+        deliveries,
+    });
+    tokio::spawn(delivery::run(state.clone()));
+
+    tracing::info!("listening on {}", listen);
+    axum::Server::bind(&listen)
+        .serve(router(state).into_make_service())
+        .await?;
+
+    Ok(())
 }
@@ -0,0 +1,90 @@
+//! Picking between ActivityStreams JSON and HTML.
+
+use axum::{
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use smacktivity_network::{is_activitystreams_media_type, webfinger::ACTIVITY_MEDIA_TYPE};
+
+/// Whether the `Accept` header asks for `application/activity+json` or
+/// `application/ld+json`. Anything else, including no `Accept` header at all,
+/// gets HTML.
+pub fn wants_activity_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter(|media_range| !refused(media_range))
+        .any(is_activitystreams_media_type)
+}
+
+// q=0 means "not this one"
+fn refused(media_range: &str) -> bool {
+    media_range
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.trim().strip_prefix("q="))
+        .any(|q| q.trim().parse::<f32>() == Ok(0.0))
+}
+
+/// A response serialized as `application/activity+json`.
+pub struct ActivityJson<T>(pub T);
+
+impl<T: serde::Serialize> IntoResponse for ActivityJson<T> {
+    fn into_response(self) -> Response {
+        match serde_json::to_vec(&self.0) {
+            Ok(body) => ([(header::CONTENT_TYPE, ACTIVITY_MEDIA_TYPE)], body).into_response(),
+            Err(err) => {
+                tracing::error!("couldn't serialize response: {}", err);
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepting(accept: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for accept in accept {
+            headers.append(header::ACCEPT, accept.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn activity_json() {
+        for accept in [
+            "application/activity+json",
+            "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"",
+            "application/ld+json",
+            "text/html;q=0.9, application/activity+json",
+            "application/activity+json;q=0.5",
+        ] {
+            assert!(wants_activity_json(&accepting(&[accept])), "{}", accept);
+        }
+        assert!(wants_activity_json(&accepting(&[
+            "text/html",
+            "application/activity+json"
+        ])));
+    }
+
+    #[test]
+    fn html() {
+        for accept in [
+            // what browsers send
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+            "*/*",
+            "application/json",
+            "application/ld+json; profile=\"https://example.com/other\"",
+            "application/activity+json;q=0",
+            "text/html, application/activity+json; q=0.0",
+        ] {
+            assert!(!wants_activity_json(&accepting(&[accept])), "{}", accept);
+        }
+        assert!(!wants_activity_json(&HeaderMap::new()));
+    }
+}
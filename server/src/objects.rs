@@ -0,0 +1,412 @@
+//! Our own objects and activities, and the edit history of everything
+//! stored.
+
+use crate::{auth::Viewer, negotiate::ActivityJson, storage::StorageError, AppState};
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+use smacktivity::{ActivityStreamsType, DateTime, LinkObject, Object};
+use std::{collections::BTreeMap, sync::Arc};
+use url::Url;
+
+/// `GET /users/{username}/statuses/{id}` and
+/// `GET /users/{username}/activities/{id}`
+pub async fn get_object(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Response {
+    let viewer = match Viewer::from_request(&state, &method, &uri, &headers).await {
+        Ok(viewer) => viewer,
+        Err(err) => return (StatusCode::UNAUTHORIZED, err.to_string()).into_response(),
+    };
+
+    let id = state.config.url(uri.path());
+    let visible = state.storage.get(&id).and_then(|object| match object {
+        Some(object) if !viewer.can_see(&state, &object)? => Ok(None),
+        Some(object) if object.has_type(ActivityStreamsType::Tombstone) => Ok(Some(object)),
+        Some(object) => hide_deleted(&state, object).map(Some),
+        None => Ok(None),
+    });
+    match visible {
+        Ok(Some(object)) if object.has_type(ActivityStreamsType::Tombstone) => {
+            (StatusCode::GONE, ActivityJson(object)).into_response()
+        }
+        Ok(Some(object)) => ActivityJson(object).into_response(),
+        // don't let on that there's something there
+        Ok(None) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(err) => {
+            tracing::error!("couldn't read {}: {}", id, err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Swap objects embedded in `activity` that have since been deleted for
+/// their Tombstones, so a Create doesn't keep serving a deleted post.
+pub fn hide_deleted(state: &AppState, mut activity: Object) -> Result<Object, StorageError> {
+    for object in activity.object.iter_mut() {
+        let id = match object.as_object().and_then(|object| object.id.as_ref()) {
+            Some(id) => id,
+            None => continue,
+        };
+        if let Some(stored) = state.storage.get(id)? {
+            if stored.has_type(ActivityStreamsType::Tombstone) {
+                *object = LinkObject::Object(Box::new(stored));
+            }
+        }
+    }
+    Ok(activity)
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevisionsQuery {
+    id: Url,
+}
+
+/// One version of an object.
+#[derive(serde::Serialize)]
+pub struct Revision {
+    /// When this version was made.
+    updated: Option<DateTime>,
+    /// The properties that differ from the version before. Edits that only
+    /// touch `updated` still show up here.
+    changes: BTreeMap<String, Change>,
+    object: Object,
+}
+
+#[derive(serde::Serialize)]
+pub struct Change {
+    old: Option<Value>,
+    new: Option<Value>,
+}
+
+/// `GET /api/revisions?id={id}`, only for the owner: every version of an
+/// object, oldest first and ending with the current one.
+pub async fn get_revisions(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RevisionsQuery>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Response {
+    match Viewer::from_request(&state, &method, &uri, &headers).await {
+        Ok(Viewer::Owner) => {}
+        _ => return StatusCode::UNAUTHORIZED.into_response(),
+    }
+
+    let versions = state.storage.revisions(&query.id).and_then(|mut versions| {
+        versions.extend(state.storage.get(&query.id)?);
+        Ok(versions)
+    });
+    match versions {
+        Ok(versions) if versions.is_empty() => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Ok(versions) => match revisions(versions) {
+            Ok(revisions) => Json(revisions).into_response(),
+            Err(err) => {
+                tracing::error!("couldn't compare revisions of {}: {}", query.id, err);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        },
+        Err(err) => {
+            tracing::error!("couldn't read revisions of {}: {}", query.id, err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn revisions(versions: Vec<Object>) -> Result<Vec<Revision>, serde_json::Error> {
+    let mut revisions = Vec::new();
+    let mut previous = serde_json::Map::new();
+    for object in versions {
+        let current = match serde_json::to_value(&object)? {
+            Value::Object(current) => current,
+            _ => serde_json::Map::new(),
+        };
+
+        let mut changes = BTreeMap::new();
+        if !revisions.is_empty() {
+            for property in previous.keys().chain(current.keys()) {
+                let (old, new) = (previous.get(property), current.get(property));
+                if old != new {
+                    changes.insert(
+                        property.clone(),
+                        Change {
+                            old: old.cloned(),
+                            new: new.cloned(),
+                        },
+                    );
+                }
+            }
+        }
+
+        revisions.push(Revision {
+            updated: object.updated.clone().or_else(|| object.published.clone()),
+            changes,
+            object,
+        });
+        previous = current;
+    }
+    Ok(revisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery, outbox, testing};
+    use serde_json::json;
+
+    fn post(state: &AppState, json: Value) -> Object {
+        let object = serde_json::from_value(json.clone()).unwrap();
+        outbox::submit(state, object, &json).unwrap()
+    }
+
+    fn posted_object(activity: &Object) -> Url {
+        activity
+            .object
+            .iter()
+            .find_map(LinkObject::href)
+            .unwrap()
+            .clone()
+    }
+
+    async fn get(state: &Arc<AppState>, id: &Url, headers: HeaderMap) -> Response {
+        let uri = id.path().parse().unwrap();
+        get_object(State(state.clone()), Method::GET, OriginalUri(uri), headers).await
+    }
+
+    fn delete(state: &AppState, id: &Url) -> Object {
+        post(state, json!({ "type": "Delete", "object": id }))
+    }
+
+    #[tokio::test]
+    async fn deleted_objects_are_gone() {
+        let state = testing::state();
+        let create = post(
+            &state,
+            json!({
+                "type": "Note",
+                "content": "hello",
+                "to": "https://www.w3.org/ns/activitystreams#Public"
+            }),
+        );
+        let note = posted_object(&create);
+        delete(&state, &note);
+
+        let response = get(&state, &note, HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+        let tombstone = testing::json(response).await;
+        assert_eq!(tombstone["type"], "Tombstone");
+        assert_eq!(tombstone["formerType"], "Note");
+        assert_eq!(tombstone["id"], note.as_str());
+    }
+
+    #[tokio::test]
+    async fn tombstones_are_as_private_as_what_they_replace() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let carol = testing::remote(&state, "https://remote.example/carol");
+        state
+            .storage
+            .append(
+                &state.config.followers(),
+                &testing::url("https://remote.example/bob"),
+            )
+            .unwrap();
+        let create = post(
+            &state,
+            json!({
+                "type": "Note",
+                "content": "just for followers",
+                "to": state.config.followers()
+            }),
+        );
+        let note = posted_object(&create);
+        delete(&state, &note);
+
+        let status = |response: Response| response.status();
+        assert_eq!(
+            status(get(&state, &note, HeaderMap::new()).await),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(get(&state, &note, testing::signed_get(&carol, &note)).await),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(get(&state, &note, testing::signed_get(&bob, &note)).await),
+            StatusCode::GONE
+        );
+        assert_eq!(
+            status(get(&state, &note, testing::owner()).await),
+            StatusCode::GONE
+        );
+    }
+
+    #[tokio::test]
+    async fn creates_hide_deleted_objects() {
+        let state = testing::state();
+        let create = post(
+            &state,
+            json!({
+                "type": "Note",
+                "content": "hello",
+                "to": "https://www.w3.org/ns/activitystreams#Public"
+            }),
+        );
+        let note = posted_object(&create);
+        let create_id = create.id.clone().unwrap();
+        let stored = state.storage.get(&create_id).unwrap().unwrap();
+        let shown = hide_deleted(&state, stored.clone()).unwrap();
+        let object = shown.object.iter().find_map(LinkObject::as_object).unwrap();
+        assert!(object.has_type(ActivityStreamsType::Note));
+
+        delete(&state, &note);
+        let shown = hide_deleted(&state, stored).unwrap();
+        let object = shown.object.iter().find_map(LinkObject::as_object).unwrap();
+        assert!(object.has_type(ActivityStreamsType::Tombstone));
+        assert_eq!(object.id.as_ref(), Some(&note));
+
+        let response = get(&state, &create_id, HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let create = testing::json(response).await;
+        assert_eq!(create["object"]["type"], "Tombstone");
+        assert!(create["object"].get("content").is_none());
+    }
+
+    #[tokio::test]
+    async fn deletes_federate_tombstones() {
+        let state = testing::state();
+        let bob = testing::url("https://remote.example/bob");
+        state
+            .storage
+            .append(&state.config.followers(), &bob)
+            .unwrap();
+        let create = post(
+            &state,
+            json!({
+                "type": "Note",
+                "content": "just for followers",
+                "to": state.config.followers(),
+                "cc": "https://remote.example/carol"
+            }),
+        );
+        let note = posted_object(&create);
+
+        let delete = delete(&state, &note);
+        assert!(delete.has_type(ActivityStreamsType::Delete));
+        let tombstone = delete
+            .object
+            .iter()
+            .find_map(LinkObject::as_object)
+            .unwrap();
+        assert!(tombstone.has_type(ActivityStreamsType::Tombstone));
+        assert_eq!(tombstone.id.as_ref(), Some(&note));
+        let stored = state.storage.get(&note).unwrap().unwrap();
+        assert!(stored.has_type(ActivityStreamsType::Tombstone));
+
+        let recipients = delivery::recipients(&state, &delete).unwrap();
+        assert!(recipients.contains(&bob));
+        assert!(recipients.contains(&testing::url("https://remote.example/carol")));
+    }
+
+    async fn revisions_of(state: &Arc<AppState>, id: &Url, headers: HeaderMap) -> Response {
+        let mut url = state.config.url("/api/revisions");
+        url.query_pairs_mut().append_pair("id", id.as_str());
+        let uri = format!("{}?{}", url.path(), url.query().unwrap());
+        let query = RevisionsQuery { id: id.clone() };
+        get_revisions(
+            State(state.clone()),
+            Query(query),
+            Method::GET,
+            OriginalUri(uri.parse().unwrap()),
+            headers,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn revisions_are_for_the_owner() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = testing::url("https://remote.example/notes/1");
+        let mut url = state.config.url("/api/revisions");
+        url.query_pairs_mut().append_pair("id", note.as_str());
+
+        for headers in [HeaderMap::new(), testing::signed_get(&bob, &url)] {
+            let response = revisions_of(&state, &note, headers).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = revisions_of(&state, &note, testing::owner()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn revisions_show_changes() {
+        let state = testing::state();
+        let note = testing::url("https://remote.example/notes/1");
+        let version = |content: &str, updated: Option<&str>| {
+            let mut version = json!({
+                "type": "Note",
+                "id": note,
+                "content": content,
+                "published": "2024-01-01T00:00:00Z"
+            });
+            if let Some(updated) = updated {
+                version["updated"] = json!(updated);
+            }
+            serde_json::from_value::<Object>(version).unwrap()
+        };
+        state.storage.put(&version("hello", None)).unwrap();
+        for (content, updated) in [
+            ("hello, edited", "2024-01-02T00:00:00Z"),
+            // how Mastodon sends some edits
+            ("hello, edited", "2024-01-03T00:00:00Z"),
+        ] {
+            state
+                .storage
+                .revise(&version(content, Some(updated)))
+                .unwrap();
+        }
+
+        let response = revisions_of(&state, &note, testing::owner()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let revisions = testing::json(response).await;
+        assert_eq!(
+            revisions[0],
+            json!({
+                "updated": "2024-01-01T00:00:00Z",
+                "changes": {},
+                "object": {
+                    "@context": "https://www.w3.org/ns/activitystreams",
+                    "type": "Note",
+                    "id": note,
+                    "content": "hello",
+                    "published": "2024-01-01T00:00:00Z"
+                }
+            })
+        );
+        assert_eq!(
+            revisions[1]["changes"],
+            json!({
+                "content": { "old": "hello", "new": "hello, edited" },
+                "updated": { "old": null, "new": "2024-01-02T00:00:00Z" }
+            })
+        );
+        assert_eq!(
+            revisions[2]["changes"],
+            json!({
+                "updated": {
+                    "old": "2024-01-02T00:00:00Z",
+                    "new": "2024-01-03T00:00:00Z"
+                }
+            })
+        );
+        assert_eq!(revisions.as_array().unwrap().len(), 3);
+    }
+}
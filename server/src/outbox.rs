@@ -0,0 +1,965 @@
+//! The user's outbox, paged like Mastodon's: the collection links to pages of
+//! `orderedItems`, newest first, with `max_id`/`min_id` cursors.
+
+use crate::{
+    auth::Viewer,
+    delivery::{self, strip_hidden_recipients},
+    follow,
+    negotiate::ActivityJson,
+    objects,
+    storage::{tombstone_of, Range, StorageError},
+    AppState,
+};
+use axum::{
+    body::Bytes,
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use smacktivity::{ActivityStreamsType, DateTime, LinkObject, NonFunctional, Object};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+pub const PAGE_SIZE: usize = 20;
+
+#[derive(serde::Deserialize)]
+pub struct PageQuery {
+    page: Option<String>,
+    /// Only items older than this.
+    max_id: Option<u64>,
+    /// Only items newer than this.
+    min_id: Option<u64>,
+}
+
+impl PageQuery {
+    fn is_page(&self) -> bool {
+        self.page.is_some() || self.max_id.is_some() || self.min_id.is_some()
+    }
+}
+
+/// `GET /users/{username}/outbox`
+pub async fn get_outbox(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    Query(query): Query<PageQuery>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Response {
+    if username != state.config.user.username {
+        return (StatusCode::NOT_FOUND, "no such user").into_response();
+    }
+
+    let viewer = match Viewer::from_request(&state, &method, &uri, &headers).await {
+        Ok(viewer) => viewer,
+        Err(err) => return (StatusCode::UNAUTHORIZED, err.to_string()).into_response(),
+    };
+
+    let outbox = state.config.outbox();
+    serve_collection(&state, &outbox, &query, visible(&state, &viewer))
+}
+
+/// The collection, or one of its pages if `query` asks for one. `item` turns
+/// each id in the collection into what goes in the page, or skips it.
+pub fn serve_collection(
+    state: &AppState,
+    id: &Url,
+    query: &PageQuery,
+    item: impl FnMut(Url) -> Result<Option<LinkObject>, StorageError>,
+) -> Response {
+    let served = if query.is_page() {
+        page(state, id, query, item)
+    } else {
+        state.storage.count(id).map(|total| collection(id, total))
+    };
+    match served {
+        Ok(served) => ActivityJson(served).into_response(),
+        Err(err) => {
+            tracing::error!("couldn't read {}: {}", id, err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Items as their ids.
+pub fn ids(id: Url) -> Result<Option<LinkObject>, StorageError> {
+    Ok(Some(LinkObject::Url(id)))
+}
+
+/// Items as the objects themselves, and only those `viewer` is allowed to
+/// see.
+pub fn visible<'a>(
+    state: &'a AppState,
+    viewer: &'a Viewer,
+) -> impl FnMut(Url) -> Result<Option<LinkObject>, StorageError> + 'a {
+    move |id| match state.storage.get(&id)? {
+        Some(object) if viewer.can_see(state, &object)? => Ok(Some(LinkObject::Object(Box::new(
+            objects::hide_deleted(state, object)?,
+        )))),
+        _ => Ok(None),
+    }
+}
+
+fn page_url(collection: &Url, cursor: Option<(&str, u64)>) -> Url {
+    let mut url = collection.clone();
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some((name, id)) = cursor {
+            query.append_pair(name, &id.to_string());
+        }
+        query.append_pair("page", "true");
+    }
+    url
+}
+
+pub fn collection(id: &Url, total_items: usize) -> Object {
+    Object {
+        type_: ActivityStreamsType::OrderedCollection.into(),
+        id: Some(id.clone()),
+        total_items: Some(total_items as u32),
+        first: Some(LinkObject::Url(page_url(id, None))),
+        last: Some(LinkObject::Url(page_url(id, Some(("min_id", 0))))),
+        ..Default::default()
+    }
+}
+
+/// One page of the collection `id`, newest first, using item positions as
+/// cursors.
+fn page(
+    state: &AppState,
+    id: &Url,
+    query: &PageQuery,
+    mut item: impl FnMut(Url) -> Result<Option<LinkObject>, StorageError>,
+) -> Result<Object, StorageError> {
+    let mut range = Range {
+        max_id: query.max_id,
+        min_id: query.min_id,
+        limit: Some(PAGE_SIZE),
+    };
+    let near_min_id = range.near_min_id();
+
+    // skipped items don't count, so keep going until the page is full
+    let mut page_items = Vec::new();
+    'scan: loop {
+        let mut batch = state.storage.items(id, range)?;
+        if batch.is_empty() {
+            break;
+        }
+        if near_min_id {
+            batch.reverse();
+        }
+        for (position, id) in batch {
+            if near_min_id {
+                range.min_id = Some(position);
+            } else {
+                range.max_id = Some(position);
+            }
+            if let Some(item) = item(id)? {
+                page_items.push((position, item));
+                if page_items.len() == PAGE_SIZE {
+                    break 'scan;
+                }
+            }
+        }
+    }
+    if near_min_id {
+        page_items.reverse();
+    }
+
+    let any = |range: Range| -> Result<bool, StorageError> {
+        let range = Range {
+            limit: Some(1),
+            ..range
+        };
+        Ok(!state.storage.items(id, range)?.is_empty())
+    };
+    let next = match page_items.last() {
+        Some((position, _))
+            if any(Range {
+                max_id: Some(*position),
+                ..Default::default()
+            })? =>
+        {
+            Some(LinkObject::Url(page_url(id, Some(("max_id", *position)))))
+        }
+        _ => None,
+    };
+    let prev = match page_items.first() {
+        Some((position, _))
+            if any(Range {
+                min_id: Some(*position),
+                ..Default::default()
+            })? =>
+        {
+            Some(LinkObject::Url(page_url(id, Some(("min_id", *position)))))
+        }
+        _ => None,
+    };
+
+    let cursor = match (query.max_id, query.min_id) {
+        (Some(max_id), _) => Some(("max_id", max_id)),
+        (None, Some(min_id)) => Some(("min_id", min_id)),
+        (None, None) => None,
+    };
+
+    Ok(Object {
+        type_: ActivityStreamsType::OrderedCollectionPage.into(),
+        id: Some(page_url(id, cursor)),
+        next,
+        prev,
+        part_of: Some(LinkObject::Url(id.clone())),
+        ordered_items: NonFunctional::Many(page_items.into_iter().map(|(_, item)| item).collect()),
+        ..Default::default()
+    })
+}
+
+#[derive(Debug)]
+pub enum OutboxError {
+    /// Only the owner can post to the outbox.
+    Unauthorized(String),
+    Json(serde_json::Error),
+    /// The activity acts on something that isn't ours.
+    Forbidden(String),
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutboxError::Unauthorized(why) => write!(f, "unauthorized: {}", why),
+            OutboxError::Json(err) => write!(f, "invalid object: {}", err),
+            OutboxError::Forbidden(why) => write!(f, "forbidden: {}", why),
+            OutboxError::Storage(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for OutboxError {}
+
+impl From<StorageError> for OutboxError {
+    fn from(err: StorageError) -> Self {
+        OutboxError::Storage(err)
+    }
+}
+
+impl IntoResponse for OutboxError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OutboxError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            OutboxError::Json(_) => StatusCode::BAD_REQUEST,
+            OutboxError::Forbidden(_) => StatusCode::FORBIDDEN,
+            OutboxError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        tracing::debug!("outbox: {}", self);
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// `POST /users/{username}/outbox`
+pub async fn post_outbox(
+    State(state): State<Arc<AppState>>,
+    Path(username): Path<String>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, OutboxError> {
+    if username != state.config.user.username {
+        return Ok((StatusCode::NOT_FOUND, "no such user").into_response());
+    }
+
+    match Viewer::from_request(&state, &method, &uri, &headers).await {
+        Ok(Viewer::Owner) => {}
+        Ok(_) => {
+            return Err(OutboxError::Unauthorized(String::from(
+                "only the owner can post to the outbox",
+            )))
+        }
+        Err(err) => return Err(OutboxError::Unauthorized(err.to_string())),
+    }
+
+    let raw: serde_json::Value = serde_json::from_slice(&body).map_err(OutboxError::Json)?;
+    let object: Object = serde_json::from_value(raw.clone()).map_err(OutboxError::Json)?;
+
+    let activity = submit(&state, object, &raw)?;
+    let id = activity
+        .id
+        .clone()
+        .expect("submitted activities have an id");
+    tracing::info!("posted {} {}", activity.type_.primary().as_str(), id);
+    delivery::deliver(state.clone(), activity);
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, id.to_string())]).into_response())
+}
+
+/// Post an activity the server made up itself, like an Accept, as the
+/// owner, and deliver it.
+pub fn publish(state: &Arc<AppState>, activity: Object) -> Result<Object, OutboxError> {
+    let activity = submit(state, activity, &serde_json::Value::Null)?;
+    delivery::deliver(state.clone(), activity.clone());
+    Ok(activity)
+}
+
+/// A new URL under the actor like `/users/alice/statuses/1234`.
+pub fn new_id(state: &AppState, kind: &str) -> Url {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let next = |last: u64| now.max(last + 1);
+    let id = LAST
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(next(last)))
+        .map_or(now, next);
+
+    state.config.url(&format!(
+        "/users/{}/{}/{}",
+        state.config.user.username, kind, id
+    ))
+}
+
+/// Handle something the owner posted: wrap bare objects in a Create, give
+/// the activity and any new objects ids, apply its side effects, and store
+/// it without `bto` and `bcc`. The returned activity still has them, for
+/// delivery.
+///
+/// `raw` is the JSON that was posted, since updates only change the
+/// properties they mention.
+pub fn submit(
+    state: &AppState,
+    object: Object,
+    raw: &serde_json::Value,
+) -> Result<Object, OutboxError> {
+    let actor = state.config.actor_id();
+    let now = DateTime::now();
+
+    let mut activity = if object.type_.primary().is_activity() {
+        object
+    } else {
+        wrap(object)
+    };
+
+    // whatever the client sent, we decide the ids
+    activity.id = Some(new_id(state, "activities"));
+    activity.actor = NonFunctional::One(LinkObject::Url(actor.clone()));
+    activity.published = Some(now.clone());
+
+    if activity.has_type(ActivityStreamsType::Create) {
+        for object in activity
+            .object
+            .iter_mut()
+            .filter_map(LinkObject::as_object_mut)
+        {
+            object.id = Some(new_id(state, "statuses"));
+            object.attributed_to = NonFunctional::One(LinkObject::Url(actor.clone()));
+            object.published = Some(now.clone());
+        }
+    }
+
+    if activity.has_type(ActivityStreamsType::Update) {
+        let updated = merge_update(state, &activity, raw.get("object"))?;
+        activity.object = NonFunctional::One(LinkObject::Object(Box::new(updated)));
+    }
+
+    if activity.has_type(ActivityStreamsType::Delete) {
+        prepare_delete(state, &mut activity)?;
+    }
+
+    follow::prepare(state, &mut activity)?;
+
+    let mut stored = activity.clone();
+    strip_hidden_recipients(&mut stored);
+    apply(state, &stored)?;
+
+    let id = stored.id.as_ref().expect("just assigned an id");
+    state.storage.put(&stored)?;
+    state.storage.append(&state.config.outbox(), id)?;
+
+    Ok(activity)
+}
+
+/// Put a bare object in a Create addressed to the same people.
+fn wrap(object: Object) -> Object {
+    Object {
+        schema_context: object.schema_context.clone(),
+        type_: ActivityStreamsType::Create.into(),
+        to: object.to.clone(),
+        cc: object.cc.clone(),
+        bto: object.bto.clone(),
+        bcc: object.bcc.clone(),
+        audience: object.audience.clone(),
+        object: NonFunctional::One(LinkObject::Object(Box::new(object))),
+        ..Default::default()
+    }
+}
+
+fn owned(state: &AppState, id: &Url) -> Result<Object, OutboxError> {
+    let actor = state.config.actor_id();
+    match state.storage.get(id)? {
+        Some(object) if object.has_type(ActivityStreamsType::Tombstone) => {
+            Err(OutboxError::Forbidden(format!("{} is deleted", id)))
+        }
+        Some(object) if crate::inbox::owned_by(&object, &actor) => Ok(object),
+        _ => Err(OutboxError::Forbidden(format!("{} isn't ours", id))),
+    }
+}
+
+// client-to-server updates only replace the top-level properties they
+// mention, and null removes one
+fn merge_update(
+    state: &AppState,
+    activity: &Object,
+    raw: Option<&serde_json::Value>,
+) -> Result<Object, OutboxError> {
+    let id = activity
+        .object
+        .iter()
+        .find_map(LinkObject::href)
+        .ok_or_else(|| OutboxError::Forbidden(String::from("update without an object")))?;
+    let stored = owned(state, id)?;
+
+    let mut merged = serde_json::to_value(&stored).map_err(OutboxError::Json)?;
+    if let (Some(merged), Some(serde_json::Value::Object(changes))) = (merged.as_object_mut(), raw)
+    {
+        for (property, value) in changes {
+            match (property.as_str(), value) {
+                ("id" | "type" | "attributedTo" | "published", _) => {}
+                (_, serde_json::Value::Null) => {
+                    merged.remove(property);
+                }
+                _ => {
+                    merged.insert(property.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    let mut merged: Object = serde_json::from_value(merged).map_err(OutboxError::Json)?;
+    merged.updated = Some(DateTime::now());
+    Ok(merged)
+}
+
+// deletes carry the object's Tombstone, and go to everyone the object did
+// unless the client said otherwise
+fn prepare_delete(state: &AppState, activity: &mut Object) -> Result<(), OutboxError> {
+    let id = activity
+        .object
+        .iter()
+        .find_map(LinkObject::href)
+        .ok_or_else(|| OutboxError::Forbidden(String::from("delete without an object")))?
+        .clone();
+    let deleted = owned(state, &id)?;
+
+    if activity.recipients().next().is_none() {
+        activity.to = deleted.to.clone();
+        activity.cc = deleted.cc.clone();
+        activity.bto = deleted.bto.clone();
+        activity.bcc = deleted.bcc.clone();
+        activity.audience = deleted.audience.clone();
+    }
+    activity.object = NonFunctional::One(LinkObject::Object(Box::new(tombstone_of(&deleted))));
+    Ok(())
+}
+
+/// The side effects of the owner's activities.
+fn apply(state: &AppState, activity: &Object) -> Result<(), OutboxError> {
+    let config = &state.config;
+    let object_id = activity.object.iter().find_map(LinkObject::href);
+
+    match activity.type_.primary() {
+        ActivityStreamsType::Create => {
+            for object in activity.object.iter().filter_map(LinkObject::as_object) {
+                state.storage.put(object)?;
+            }
+        }
+        ActivityStreamsType::Update => {
+            for object in activity.object.iter().filter_map(LinkObject::as_object) {
+                state.storage.revise(object)?;
+            }
+        }
+        // checked and turned into a Tombstone by prepare_delete
+        ActivityStreamsType::Delete => {
+            for tombstone in activity.object.iter().filter_map(LinkObject::as_object) {
+                state.storage.put(tombstone)?;
+            }
+        }
+        ActivityStreamsType::Like => {
+            if let Some(id) = object_id {
+                state.storage.append(&config.liked(), id)?;
+            }
+        }
+        ActivityStreamsType::Block => {
+            if let Some(id) = object_id {
+                follow::sever(state, id)?;
+            }
+        }
+        ActivityStreamsType::Follow => follow::sent(state, activity)?,
+        ActivityStreamsType::Accept | ActivityStreamsType::Reject => {
+            let follow = follow::follow_of(state, activity)?
+                .filter(|follow| {
+                    follow.object.iter().find_map(LinkObject::href) == Some(&config.actor_id())
+                })
+                .ok_or_else(|| OutboxError::Forbidden(String::from("not a follow of ours")))?;
+            if activity.has_type(ActivityStreamsType::Accept) {
+                follow::approve(state, &follow)?;
+            } else {
+                follow::deny(state, &follow)?;
+            }
+        }
+        ActivityStreamsType::Undo => {
+            let undone = match object_id {
+                Some(id) => owned(state, id)?,
+                None => return Ok(()),
+            };
+            let target = undone.object.iter().find_map(LinkObject::href);
+            match (undone.type_.primary(), target) {
+                (ActivityStreamsType::Like, Some(target)) => {
+                    state.storage.remove(&config.liked(), target)?
+                }
+                (ActivityStreamsType::Follow, Some(target)) => follow::unfollowing(state, target)?,
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use serde_json::json;
+
+    const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+    /// Post `count` notes straight into the outbox, the ones `private`
+    /// picks only to followers.
+    fn fill(state: &AppState, count: usize, private: impl Fn(usize) -> bool) -> Vec<Url> {
+        (0..count)
+            .map(|n| {
+                let id = state.config.url(&format!("/users/alice/statuses/{}", n));
+                let to = if private(n) {
+                    json!(state.config.followers())
+                } else {
+                    json!(PUBLIC)
+                };
+                let note = json!({ "type": "Note", "id": id, "to": to });
+                state
+                    .storage
+                    .put(&serde_json::from_value(note).unwrap())
+                    .unwrap();
+                state.storage.append(&state.config.outbox(), &id).unwrap();
+                id
+            })
+            .collect()
+    }
+
+    fn first_page() -> PageQuery {
+        PageQuery {
+            page: Some(String::from("true")),
+            max_id: None,
+            min_id: None,
+        }
+    }
+
+    /// The query a `next` or `prev` link asks for.
+    fn follow(link: &Option<LinkObject>) -> PageQuery {
+        let link = link.as_ref().and_then(LinkObject::href).unwrap();
+        let cursor = |name: &str| {
+            link.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.parse().unwrap())
+        };
+        PageQuery {
+            page: Some(String::from("true")),
+            max_id: cursor("max_id"),
+            min_id: cursor("min_id"),
+        }
+    }
+
+    fn outbox_page(state: &AppState, viewer: &Viewer, query: &PageQuery) -> Object {
+        page(state, &state.config.outbox(), query, visible(state, viewer)).unwrap()
+    }
+
+    fn item_ids(page: &Object) -> Vec<Url> {
+        page.ordered_items
+            .iter()
+            .filter_map(LinkObject::href)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn empty_outboxes() {
+        let state = testing::state();
+        let outbox = state.config.outbox();
+
+        let page = outbox_page(&state, &Viewer::Anonymous, &first_page());
+        assert!(page.ordered_items.iter().next().is_none());
+        assert!(page.next.is_none());
+        assert!(page.prev.is_none());
+        assert_eq!(
+            page.part_of.as_ref().and_then(LinkObject::href),
+            Some(&outbox)
+        );
+
+        let collection = collection(&outbox, state.storage.count(&outbox).unwrap());
+        assert_eq!(collection.total_items, Some(0));
+        assert!(collection.first.is_some());
+    }
+
+    #[test]
+    fn pages_link_to_each_other() {
+        let state = testing::state();
+        let notes = fill(&state, PAGE_SIZE + 5, |_| false);
+        let newest_first: Vec<Url> = notes.into_iter().rev().collect();
+
+        let first = outbox_page(&state, &Viewer::Anonymous, &first_page());
+        assert_eq!(item_ids(&first), newest_first[..PAGE_SIZE]);
+        assert!(first.prev.is_none());
+
+        let second = outbox_page(&state, &Viewer::Anonymous, &follow(&first.next));
+        assert_eq!(item_ids(&second), newest_first[PAGE_SIZE..]);
+        assert!(second.next.is_none());
+
+        let back = outbox_page(&state, &Viewer::Anonymous, &follow(&second.prev));
+        assert_eq!(item_ids(&back), item_ids(&first));
+        assert!(back.prev.is_none());
+        assert!(back.next.is_some());
+    }
+
+    #[test]
+    fn pages_skip_what_the_viewer_cant_see() {
+        let state = testing::state();
+        // a third of them only for followers
+        let notes = fill(&state, 2 * PAGE_SIZE, |n| n % 3 == 0);
+        let public: Vec<Url> = notes
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(n, _)| n % 3 != 0)
+            .map(|(_, id)| id.clone())
+            .collect();
+
+        let first = outbox_page(&state, &Viewer::Anonymous, &first_page());
+        assert_eq!(item_ids(&first), public[..PAGE_SIZE]);
+        let second = outbox_page(&state, &Viewer::Anonymous, &follow(&first.next));
+        assert_eq!(item_ids(&second), public[PAGE_SIZE..]);
+
+        let owners = outbox_page(&state, &Viewer::Owner, &first_page());
+        let newest_first: Vec<Url> = notes.into_iter().rev().collect();
+        assert_eq!(item_ids(&owners), newest_first[..PAGE_SIZE]);
+    }
+
+    async fn post(
+        state: &Arc<AppState>,
+        headers: HeaderMap,
+        json: serde_json::Value,
+    ) -> Result<Response, OutboxError> {
+        let outbox = state.config.outbox();
+        post_outbox(
+            State(state.clone()),
+            Path(String::from("alice")),
+            Method::POST,
+            OriginalUri(outbox.path().parse().unwrap()),
+            headers,
+            Bytes::from(serde_json::to_vec(&json).unwrap()),
+        )
+        .await
+    }
+
+    /// The activity a `201 Created` response points at.
+    fn created(state: &AppState, response: Response) -> Object {
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        state.storage.get(&testing::url(location)).unwrap().unwrap()
+    }
+
+    fn embedded(activity: &Object) -> &Object {
+        activity
+            .object
+            .iter()
+            .find_map(LinkObject::as_object)
+            .unwrap()
+    }
+
+    fn hrefs(links: &NonFunctional<LinkObject>) -> Vec<&str> {
+        links
+            .iter()
+            .filter_map(LinkObject::href)
+            .map(Url::as_str)
+            .collect()
+    }
+
+    const ALICE: &str = "https://local.example/users/alice";
+
+    #[tokio::test]
+    async fn bare_objects_are_created() {
+        let state = testing::state();
+        let response = post(
+            &state,
+            testing::owner(),
+            json!({
+                "type": "Note",
+                "id": "https://remote.example/notes/1",
+                "attributedTo": "https://remote.example/bob",
+                "content": "hello",
+                "to": PUBLIC
+            }),
+        )
+        .await
+        .unwrap();
+
+        let create = created(&state, response);
+        assert!(create.has_type(ActivityStreamsType::Create));
+        assert_eq!(hrefs(&create.actor), [ALICE]);
+        assert!(create.published.is_some());
+        assert_eq!(hrefs(&create.to), [PUBLIC]);
+        let id = create.id.clone().unwrap();
+        assert!(id.path().starts_with("/users/alice/activities/"));
+        assert!(state
+            .storage
+            .in_collection(&state.config.outbox(), &id)
+            .unwrap());
+
+        // the client doesn't get to pick the id or the author
+        let note = embedded(&create);
+        let note_id = note.id.clone().unwrap();
+        assert!(note_id.path().starts_with("/users/alice/statuses/"));
+        assert_eq!(hrefs(&note.attributed_to), [ALICE]);
+        assert_eq!(note.published, create.published);
+        let stored = state.storage.get(&note_id).unwrap().unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello");
+        assert!(!state
+            .storage
+            .contains(&testing::url("https://remote.example/notes/1"))
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn hidden_recipients_are_delivered_to_but_not_stored() {
+        let state = testing::state();
+        for actor in ["bob", "carol"] {
+            state.deliveries.cache_inbox(
+                &testing::url(&format!("https://remote.example/{}", actor)),
+                &testing::url(&format!("https://remote.example/{}/inbox", actor)),
+            );
+        }
+
+        let response = post(
+            &state,
+            testing::owner(),
+            json!({
+                "type": "Note",
+                "content": "psst",
+                "bto": "https://remote.example/bob",
+                "bcc": "https://remote.example/carol"
+            }),
+        )
+        .await
+        .unwrap();
+
+        let create = created(&state, response);
+        assert!(create.bto.is_none() && create.bcc.is_none());
+        let note = embedded(&create);
+        assert!(note.bto.is_none() && note.bcc.is_none());
+        let stored = state
+            .storage
+            .get(note.id.as_ref().unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(stored.bto.is_none() && stored.bcc.is_none());
+
+        for _ in 0..500 {
+            if state.deliveries.pending().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let mut inboxes: Vec<String> = state
+            .deliveries
+            .pending()
+            .into_iter()
+            .map(|delivery| {
+                assert!(delivery.activity.bto.is_none() && delivery.activity.bcc.is_none());
+                delivery.inbox.to_string()
+            })
+            .collect();
+        inboxes.sort();
+        assert_eq!(
+            inboxes,
+            [
+                "https://remote.example/bob/inbox",
+                "https://remote.example/carol/inbox"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_merge_into_the_stored_object() {
+        let state = testing::state();
+        let create = created(
+            &state,
+            post(
+                &state,
+                testing::owner(),
+                json!({
+                    "type": "Note",
+                    "content": "hello",
+                    "summary": "greetings",
+                    "to": PUBLIC
+                }),
+            )
+            .await
+            .unwrap(),
+        );
+        let note = embedded(&create).clone();
+        let id = note.id.clone().unwrap();
+
+        let update = created(
+            &state,
+            post(
+                &state,
+                testing::owner(),
+                json!({
+                    "type": "Update",
+                    "object": {
+                        "id": id,
+                        "attributedTo": "https://remote.example/bob",
+                        "content": "hello again",
+                        "summary": null
+                    }
+                }),
+            )
+            .await
+            .unwrap(),
+        );
+        assert!(update.has_type(ActivityStreamsType::Update));
+
+        let stored = state.storage.get(&id).unwrap().unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello again");
+        assert!(stored.summary.is_none());
+        assert_eq!(hrefs(&stored.to), [PUBLIC]);
+        assert_eq!(hrefs(&stored.attributed_to), [ALICE]);
+        assert_eq!(stored.published, note.published);
+        assert!(stored.updated.is_some());
+        assert_eq!(
+            embedded(&update).content.iter().next().unwrap(),
+            "hello again"
+        );
+
+        let revisions = state.storage.revisions(&id).unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].content.iter().next().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn deletes_leave_tombstones() {
+        let state = testing::state();
+        let create = created(
+            &state,
+            post(
+                &state,
+                testing::owner(),
+                json!({ "type": "Note", "content": "hello", "to": PUBLIC }),
+            )
+            .await
+            .unwrap(),
+        );
+        let id = embedded(&create).id.clone().unwrap();
+
+        let delete = created(
+            &state,
+            post(
+                &state,
+                testing::owner(),
+                json!({ "type": "Delete", "object": id }),
+            )
+            .await
+            .unwrap(),
+        );
+        assert!(delete.has_type(ActivityStreamsType::Delete));
+        assert_eq!(hrefs(&delete.to), [PUBLIC]);
+        let tombstone = embedded(&delete);
+        assert!(tombstone.has_type(ActivityStreamsType::Tombstone));
+        assert_eq!(tombstone.id.as_ref(), Some(&id));
+
+        let stored = state.storage.get(&id).unwrap().unwrap();
+        assert!(stored.has_type(ActivityStreamsType::Tombstone));
+        assert_eq!(
+            stored.former_type.iter().next(),
+            Some(&ActivityStreamsType::Note)
+        );
+        assert!(stored.content.iter().next().is_none());
+
+        // and it stays deleted
+        let again = post(
+            &state,
+            testing::owner(),
+            json!({ "type": "Delete", "object": id }),
+        )
+        .await;
+        assert!(matches!(again, Err(OutboxError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn only_our_own_objects_change() {
+        let state = testing::state();
+        let theirs = json!({
+            "type": "Note",
+            "id": "https://remote.example/notes/1",
+            "attributedTo": "https://remote.example/bob",
+            "content": "hello"
+        });
+        state
+            .storage
+            .put(&serde_json::from_value(theirs).unwrap())
+            .unwrap();
+
+        for activity in [
+            json!({
+                "type": "Update",
+                "object": { "id": "https://remote.example/notes/1", "content": "mine now" }
+            }),
+            json!({ "type": "Delete", "object": "https://remote.example/notes/1" }),
+        ] {
+            let err = post(&state, testing::owner(), activity).await.unwrap_err();
+            assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+        }
+        let stored = state
+            .storage
+            .get(&testing::url("https://remote.example/notes/1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.content.iter().next().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_posts() {
+        let state = testing::state();
+        let bob = testing::remote(&state, "https://remote.example/bob");
+        let note = json!({ "type": "Note", "content": "hello", "to": PUBLIC });
+        let body = serde_json::to_vec(&note).unwrap();
+        let mut wrong_token = HeaderMap::new();
+        wrong_token.insert("authorization", "Bearer nope".parse().unwrap());
+
+        for headers in [
+            HeaderMap::new(),
+            wrong_token,
+            testing::signed(&bob, &state.config.outbox(), &body),
+        ] {
+            let err = post(&state, headers, note.clone()).await.unwrap_err();
+            assert_eq!(err.into_response().status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(state.storage.count(&state.config.outbox()).unwrap(), 0);
+    }
+}
@@ -0,0 +1,447 @@
+//! Where the server keeps objects, activities and collections.
+//!
+//! Objects are kept as their JSON, keyed by `id`. Collections are lists of
+//! ids, each item with a position that only ever grows, so positions work as
+//! paging cursors even after items are removed. Objects that are updated
+//! keep their earlier versions as revisions.
+
+mod memory;
+mod sqlite;
+
+pub use memory::Memory;
+pub use sqlite::Sqlite;
+
+use crate::config::{Config, StorageConfig};
+use smacktivity::{ActivityStreamsType, DateTime, NonFunctional, Object};
+use url::Url;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Only objects with an `id` can be stored.
+    MissingId,
+    Json(serde_json::Error),
+    Sqlite(rusqlite::Error),
+    /// A collection item that isn't a URL.
+    Url(url::ParseError),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::MissingId => write!(f, "object has no id"),
+            StorageError::Json(err) => write!(f, "couldn't (de)serialize object: {}", err),
+            StorageError::Sqlite(err) => write!(f, "database error: {}", err),
+            StorageError::Url(err) => write!(f, "invalid collection item: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Json(err)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Sqlite(err)
+    }
+}
+
+/// Which items of a collection to get, like Mastodon's paging parameters.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Range {
+    /// Only items older than this position.
+    pub max_id: Option<u64>,
+    /// Only items newer than this position. Without `max_id`, the ones just
+    /// newer than it rather than the newest.
+    pub min_id: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl Range {
+    /// Whether the items closest to `min_id` are wanted.
+    pub fn near_min_id(&self) -> bool {
+        self.max_id.is_none() && self.min_id.is_some()
+    }
+}
+
+pub trait Storage: Send + Sync {
+    fn contains(&self, id: &Url) -> Result<bool, StorageError>;
+
+    fn get(&self, id: &Url) -> Result<Option<Object>, StorageError>;
+
+    /// Store an object, replacing whatever had the same `id`.
+    fn put(&self, object: &Object) -> Result<(), StorageError>;
+
+    /// Add `item` to the end of a collection, if it isn't there already.
+    fn append(&self, collection: &Url, item: &Url) -> Result<(), StorageError>;
+
+    fn remove(&self, collection: &Url, item: &Url) -> Result<(), StorageError>;
+
+    fn in_collection(&self, collection: &Url, item: &Url) -> Result<bool, StorageError>;
+
+    fn count(&self, collection: &Url) -> Result<usize, StorageError>;
+
+    /// Items in `range` with their positions, newest first.
+    fn items(&self, collection: &Url, range: Range) -> Result<Vec<(u64, Url)>, StorageError>;
+
+    /// Keep `object` as a past version of itself.
+    fn add_revision(&self, object: &Object) -> Result<(), StorageError>;
+
+    /// Past versions of an object, oldest first.
+    fn revisions(&self, id: &Url) -> Result<Vec<Object>, StorageError>;
+
+    /// Store a new version of an object, keeping the one it replaces as a
+    /// revision.
+    fn revise(&self, object: &Object) -> Result<(), StorageError> {
+        let id = object.id.as_ref().ok_or(StorageError::MissingId)?;
+        if let Some(previous) = self.get(id)? {
+            self.add_revision(&previous)?;
+        }
+        self.put(object)
+    }
+
+    /// Replace an object with a Tombstone, so it's remembered as deleted.
+    /// Already deleted objects are left alone.
+    fn tombstone(&self, id: &Url) -> Result<(), StorageError> {
+        let tombstone = match self.get(id)? {
+            Some(object) if object.has_type(ActivityStreamsType::Tombstone) => return Ok(()),
+            Some(object) => tombstone_of(&object),
+            None => Object {
+                type_: ActivityStreamsType::Tombstone.into(),
+                id: Some(id.clone()),
+                deleted: Some(DateTime::now()),
+                ..Default::default()
+            },
+        };
+        self.put(&tombstone)
+    }
+
+    /// Everything in a collection, oldest first.
+    fn collection(&self, collection: &Url) -> Result<Vec<Url>, StorageError> {
+        let mut items = self.items(collection, Range::default())?;
+        items.reverse();
+        Ok(items.into_iter().map(|(_, item)| item).collect())
+    }
+}
+
+/// What's left of `object` once it's deleted.
+pub fn tombstone_of(object: &Object) -> Object {
+    Object {
+        type_: ActivityStreamsType::Tombstone.into(),
+        id: object.id.clone(),
+        former_type: NonFunctional::One(object.type_.primary().clone()),
+        deleted: Some(DateTime::now()),
+        // kept so the Tombstone is only shown to whoever could see the
+        // object it replaced
+        to: object.to.clone(),
+        cc: object.cc.clone(),
+        audience: object.audience.clone(),
+        ..Default::default()
+    }
+}
+
+/// The backend from the config.
+pub fn open(config: &Config) -> Result<Box<dyn Storage>, StorageError> {
+    match &config.storage {
+        StorageConfig::Memory => Ok(Box::new(Memory::new())),
+        StorageConfig::Sqlite { path } => {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| config.data_dir.join("smacktivity.db"));
+            Ok(Box::new(Sqlite::open(path)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::url;
+    use serde_json::json;
+
+    // every test runs against each backend
+    fn backends() -> [(&'static str, Box<dyn Storage>); 2] {
+        [
+            ("memory", Box::new(Memory::new())),
+            ("sqlite", Box::new(Sqlite::open(":memory:").unwrap())),
+        ]
+    }
+
+    fn note(id: &str, content: &str) -> Object {
+        serde_json::from_value(json!({
+            "type": "Note",
+            "id": id,
+            "content": content,
+            "published": "2023-06-23T07:17:56+02:00"
+        }))
+        .unwrap()
+    }
+
+    fn content(object: &Object) -> &str {
+        object.content.iter().next().unwrap()
+    }
+
+    #[test]
+    fn objects() {
+        for (backend, storage) in backends() {
+            let id = url("https://local.example/notes/1");
+            assert!(!storage.contains(&id).unwrap(), "{}", backend);
+            assert!(storage.get(&id).unwrap().is_none(), "{}", backend);
+
+            storage.put(&note(id.as_str(), "first")).unwrap();
+            storage.put(&note(id.as_str(), "second")).unwrap();
+            assert!(storage.contains(&id).unwrap(), "{}", backend);
+            let stored = storage.get(&id).unwrap().unwrap();
+            assert_eq!(content(&stored), "second", "{}", backend);
+            assert!(storage.revisions(&id).unwrap().is_empty(), "{}", backend);
+
+            assert!(
+                matches!(
+                    storage.put(&Object::default()),
+                    Err(StorageError::MissingId)
+                ),
+                "{}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn collections() {
+        for (backend, storage) in backends() {
+            let collection = url("https://local.example/users/alice/followers");
+            let bob = url("https://remote.example/bob");
+            let carol = url("https://remote.example/carol");
+
+            storage.append(&collection, &bob).unwrap();
+            storage.append(&collection, &carol).unwrap();
+            storage.append(&collection, &bob).unwrap();
+            assert_eq!(storage.count(&collection).unwrap(), 2, "{}", backend);
+            assert_eq!(
+                storage.collection(&collection).unwrap(),
+                [bob.clone(), carol.clone()],
+                "{}",
+                backend
+            );
+
+            storage.remove(&collection, &bob).unwrap();
+            assert!(
+                !storage.in_collection(&collection, &bob).unwrap(),
+                "{}",
+                backend
+            );
+            assert!(
+                storage.in_collection(&collection, &carol).unwrap(),
+                "{}",
+                backend
+            );
+            assert_eq!(storage.count(&collection).unwrap(), 1, "{}", backend);
+
+            // other collections are separate
+            let other = url("https://local.example/users/alice/following");
+            assert_eq!(storage.count(&other).unwrap(), 0, "{}", backend);
+            assert!(
+                !storage.in_collection(&other, &carol).unwrap(),
+                "{}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn paging() {
+        for (backend, storage) in backends() {
+            let collection = url("https://local.example/users/alice/outbox");
+            let items = (1..=5)
+                .map(|n| url(&format!("https://local.example/activities/{}", n)))
+                .collect::<Vec<_>>();
+            for item in &items {
+                storage.append(&collection, item).unwrap();
+            }
+            // another collection's items take up positions in between
+            storage
+                .append(&url("https://local.example/other"), &items[0])
+                .unwrap();
+
+            let all = storage.items(&collection, Range::default()).unwrap();
+            let positions = all
+                .iter()
+                .map(|(position, _)| *position)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                all.into_iter().map(|(_, item)| item).collect::<Vec<_>>(),
+                items.iter().rev().cloned().collect::<Vec<_>>(),
+                "{}",
+                backend
+            );
+            assert!(
+                positions.windows(2).all(|pair| pair[0] > pair[1]),
+                "{}",
+                backend
+            );
+            // positions are oldest to newest: [5, 4, 3, 2, 1] -> by item number
+            let position = |n: usize| positions[5 - n];
+
+            let page = |range: Range| {
+                storage
+                    .items(&collection, range)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(_, item)| item)
+                    .collect::<Vec<_>>()
+            };
+            let expected = |numbers: &[usize]| {
+                numbers
+                    .iter()
+                    .map(|n| items[n - 1].clone())
+                    .collect::<Vec<_>>()
+            };
+
+            let newest = Range {
+                limit: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(page(newest), expected(&[5, 4]), "{}", backend);
+
+            let older = Range {
+                max_id: Some(position(4)),
+                limit: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(page(older), expected(&[3, 2]), "{}", backend);
+
+            // just newer than min_id, still newest first
+            let newer = Range {
+                min_id: Some(position(2)),
+                limit: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(page(newer), expected(&[4, 3]), "{}", backend);
+
+            let between = Range {
+                max_id: Some(position(5)),
+                min_id: Some(position(1)),
+                limit: None,
+            };
+            assert_eq!(page(between), expected(&[4, 3, 2]), "{}", backend);
+
+            // removing items doesn't move the others
+            storage.remove(&collection, &items[2]).unwrap();
+            assert_eq!(page(older), expected(&[2, 1]), "{}", backend);
+        }
+    }
+
+    #[test]
+    fn cursors_past_i64() {
+        for (backend, storage) in backends() {
+            let collection = url("https://local.example/users/alice/outbox");
+            let item = url("https://local.example/activities/1");
+            storage.append(&collection, &item).unwrap();
+
+            let huge = i64::MAX as u64 + 1;
+            let before_huge = Range {
+                max_id: Some(huge),
+                ..Default::default()
+            };
+            assert_eq!(
+                storage.items(&collection, before_huge).unwrap().len(),
+                1,
+                "{}",
+                backend
+            );
+            let after_huge = Range {
+                min_id: Some(u64::MAX),
+                ..Default::default()
+            };
+            assert!(
+                storage.items(&collection, after_huge).unwrap().is_empty(),
+                "{}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn revisions() {
+        for (backend, storage) in backends() {
+            let id = url("https://remote.example/notes/1");
+            storage.revise(&note(id.as_str(), "first")).unwrap();
+            assert!(storage.revisions(&id).unwrap().is_empty(), "{}", backend);
+
+            storage.revise(&note(id.as_str(), "second")).unwrap();
+            storage.revise(&note(id.as_str(), "third")).unwrap();
+            assert_eq!(
+                content(&storage.get(&id).unwrap().unwrap()),
+                "third",
+                "{}",
+                backend
+            );
+            let revisions = storage.revisions(&id).unwrap();
+            assert_eq!(
+                revisions.iter().map(content).collect::<Vec<_>>(),
+                ["first", "second"],
+                "{}",
+                backend
+            );
+            assert!(
+                storage
+                    .revisions(&url("https://remote.example/notes/2"))
+                    .unwrap()
+                    .is_empty(),
+                "{}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn tombstones() {
+        for (backend, storage) in backends() {
+            let id = url("https://local.example/notes/1");
+            storage.put(&note(id.as_str(), "hello")).unwrap();
+            storage.tombstone(&id).unwrap();
+
+            let tombstone = storage.get(&id).unwrap().unwrap();
+            assert!(
+                tombstone.has_type(ActivityStreamsType::Tombstone),
+                "{}",
+                backend
+            );
+            assert_eq!(
+                tombstone.former_type.iter().next(),
+                Some(&ActivityStreamsType::Note),
+                "{}",
+                backend
+            );
+            assert!(tombstone.content.is_none(), "{}", backend);
+            assert!(tombstone.deleted.is_some(), "{}", backend);
+
+            // deleting again keeps the first deletion
+            let mut earlier = tombstone.clone();
+            earlier.deleted = Some(DateTime::parse("2000-01-01T00:00:00Z").unwrap());
+            storage.put(&earlier).unwrap();
+            storage.tombstone(&id).unwrap();
+            assert_eq!(
+                storage.get(&id).unwrap().unwrap().deleted,
+                earlier.deleted,
+                "{}",
+                backend
+            );
+
+            // things we never had are remembered as deleted too
+            let unknown = url("https://local.example/notes/2");
+            storage.tombstone(&unknown).unwrap();
+            let tombstone = storage.get(&unknown).unwrap().unwrap();
+            assert!(
+                tombstone.has_type(ActivityStreamsType::Tombstone),
+                "{}",
+                backend
+            );
+            assert!(tombstone.former_type.is_none(), "{}", backend);
+        }
+    }
+}
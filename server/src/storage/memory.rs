@@ -0,0 +1,131 @@
+//! Storage that's gone when the server stops, for trying things out.
+
+use super::{Range, Storage, StorageError};
+use smacktivity::Object;
+use std::{collections::HashMap, sync::Mutex};
+use url::Url;
+
+#[derive(Default)]
+struct Collections {
+    /// The position of the last item appended to any collection.
+    last_position: u64,
+    /// Items with their positions, oldest first.
+    items: HashMap<Url, Vec<(u64, Url)>>,
+}
+
+#[derive(Default)]
+pub struct Memory {
+    objects: Mutex<HashMap<Url, String>>,
+    collections: Mutex<Collections>,
+    /// Past versions of objects as JSON, oldest first.
+    revisions: Mutex<HashMap<Url, Vec<String>>>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+}
+
+impl Storage for Memory {
+    fn contains(&self, id: &Url) -> Result<bool, StorageError> {
+        Ok(self.objects.lock().unwrap().contains_key(id))
+    }
+
+    fn get(&self, id: &Url) -> Result<Option<Object>, StorageError> {
+        match self.objects.lock().unwrap().get(id) {
+            Some(json) => Ok(Some(serde_json::from_str(json)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&self, object: &Object) -> Result<(), StorageError> {
+        let id = object.id.clone().ok_or(StorageError::MissingId)?;
+        let json = serde_json::to_string(object)?;
+        self.objects.lock().unwrap().insert(id, json);
+        Ok(())
+    }
+
+    fn add_revision(&self, object: &Object) -> Result<(), StorageError> {
+        let id = object.id.clone().ok_or(StorageError::MissingId)?;
+        let json = serde_json::to_string(object)?;
+        self.revisions
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_default()
+            .push(json);
+        Ok(())
+    }
+
+    fn revisions(&self, id: &Url) -> Result<Vec<Object>, StorageError> {
+        let revisions = self.revisions.lock().unwrap();
+        let mut objects = Vec::new();
+        for json in revisions.get(id).into_iter().flatten() {
+            objects.push(serde_json::from_str(json)?);
+        }
+        Ok(objects)
+    }
+
+    fn append(&self, collection: &Url, item: &Url) -> Result<(), StorageError> {
+        let mut collections = self.collections.lock().unwrap();
+        let position = collections.last_position + 1;
+        let items = collections.items.entry(collection.clone()).or_default();
+        if !items.iter().any(|(_, existing)| existing == item) {
+            items.push((position, item.clone()));
+            collections.last_position = position;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, collection: &Url, item: &Url) -> Result<(), StorageError> {
+        if let Some(items) = self.collections.lock().unwrap().items.get_mut(collection) {
+            items.retain(|(_, existing)| existing != item);
+        }
+        Ok(())
+    }
+
+    fn in_collection(&self, collection: &Url, item: &Url) -> Result<bool, StorageError> {
+        Ok(self
+            .collections
+            .lock()
+            .unwrap()
+            .items
+            .get(collection)
+            .is_some_and(|items| items.iter().any(|(_, existing)| existing == item)))
+    }
+
+    fn count(&self, collection: &Url) -> Result<usize, StorageError> {
+        Ok(self
+            .collections
+            .lock()
+            .unwrap()
+            .items
+            .get(collection)
+            .map_or(0, Vec::len))
+    }
+
+    fn items(&self, collection: &Url, range: Range) -> Result<Vec<(u64, Url)>, StorageError> {
+        let collections = self.collections.lock().unwrap();
+        let items = match collections.items.get(collection) {
+            Some(items) => items,
+            None => return Ok(Vec::new()),
+        };
+
+        let max_id = range.max_id.unwrap_or(u64::MAX);
+        let min_id = range.min_id.unwrap_or(0);
+        let limit = range.limit.unwrap_or(usize::MAX);
+        let in_range = items
+            .iter()
+            .filter(|(position, _)| min_id < *position && *position < max_id)
+            .cloned();
+
+        if range.near_min_id() {
+            let mut items = in_range.take(limit).collect::<Vec<_>>();
+            items.reverse();
+            Ok(items)
+        } else {
+            Ok(in_range.rev().take(limit).collect())
+        }
+    }
+}
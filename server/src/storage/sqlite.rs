@@ -0,0 +1,211 @@
+//! Storage in an SQLite database.
+//!
+//! Objects are kept as JSON next to a few columns pulled out of them, so
+//! they can be looked up by type, date, author or what they reply to. Past
+//! versions of objects are kept in their own table.
+
+use super::{Range, Storage, StorageError};
+use rusqlite::{params, Connection, OptionalExtension};
+use smacktivity::{LinkObject, Object};
+use std::{path::Path, sync::Mutex};
+use url::Url;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS objects (
+    id TEXT PRIMARY KEY NOT NULL,
+    type TEXT NOT NULL,
+    published TEXT,
+    attributed_to TEXT,
+    in_reply_to TEXT,
+    json TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS objects_type ON objects (type);
+CREATE INDEX IF NOT EXISTS objects_published ON objects (published);
+CREATE INDEX IF NOT EXISTS objects_attributed_to ON objects (attributed_to);
+CREATE INDEX IF NOT EXISTS objects_in_reply_to ON objects (in_reply_to);
+
+CREATE TABLE IF NOT EXISTS collection_items (
+    position INTEGER PRIMARY KEY AUTOINCREMENT,
+    collection TEXT NOT NULL,
+    item TEXT NOT NULL,
+    UNIQUE (collection, item)
+);
+
+CREATE TABLE IF NOT EXISTS revisions (
+    revision INTEGER PRIMARY KEY AUTOINCREMENT,
+    object_id TEXT NOT NULL,
+    json TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS revisions_object_id ON revisions (object_id);
+";
+
+pub struct Sqlite {
+    connection: Mutex<Connection>,
+}
+
+impl Sqlite {
+    pub fn open(path: impl AsRef<Path>) -> Result<Sqlite, StorageError> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Sqlite {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+fn first_href(property: &smacktivity::NonFunctional<LinkObject>) -> Option<&str> {
+    property.iter().find_map(LinkObject::href).map(Url::as_str)
+}
+
+impl Storage for Sqlite {
+    fn contains(&self, id: &Url) -> Result<bool, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let found = connection
+            .query_row("SELECT 1 FROM objects WHERE id = ?1", [id.as_str()], |_| {
+                Ok(())
+            })
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    fn get(&self, id: &Url) -> Result<Option<Object>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let json = connection
+            .query_row(
+                "SELECT json FROM objects WHERE id = ?1",
+                [id.as_str()],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        match json {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&self, object: &Object) -> Result<(), StorageError> {
+        let id = object.id.as_ref().ok_or(StorageError::MissingId)?;
+        let json = serde_json::to_string(object)?;
+        // normalized to UTC so the column sorts by time
+        let published = object.published.as_ref().map(|published| {
+            published
+                .to_utc()
+                .map(|utc| utc.to_rfc3339())
+                .unwrap_or_else(|| String::from(published.as_str()))
+        });
+
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO objects
+                (id, type, published, attributed_to, in_reply_to, json)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id.as_str(),
+                object.type_.primary().as_str(),
+                published,
+                first_href(&object.attributed_to),
+                first_href(&object.in_reply_to),
+                json,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn add_revision(&self, object: &Object) -> Result<(), StorageError> {
+        let id = object.id.as_ref().ok_or(StorageError::MissingId)?;
+        let json = serde_json::to_string(object)?;
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO revisions (object_id, json) VALUES (?1, ?2)",
+            [id.as_str(), json.as_str()],
+        )?;
+        Ok(())
+    }
+
+    fn revisions(&self, id: &Url) -> Result<Vec<Object>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT json FROM revisions WHERE object_id = ?1 ORDER BY revision ASC",
+        )?;
+        let rows = statement.query_map([id.as_str()], |row| row.get::<_, String>(0))?;
+
+        let mut revisions = Vec::new();
+        for json in rows {
+            revisions.push(serde_json::from_str(&json?)?);
+        }
+        Ok(revisions)
+    }
+
+    fn append(&self, collection: &Url, item: &Url) -> Result<(), StorageError> {
+        self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO collection_items (collection, item) VALUES (?1, ?2)",
+            [collection.as_str(), item.as_str()],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, collection: &Url, item: &Url) -> Result<(), StorageError> {
+        self.connection.lock().unwrap().execute(
+            "DELETE FROM collection_items WHERE collection = ?1 AND item = ?2",
+            [collection.as_str(), item.as_str()],
+        )?;
+        Ok(())
+    }
+
+    fn in_collection(&self, collection: &Url, item: &Url) -> Result<bool, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let found = connection
+            .query_row(
+                "SELECT 1 FROM collection_items WHERE collection = ?1 AND item = ?2",
+                [collection.as_str(), item.as_str()],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    fn count(&self, collection: &Url) -> Result<usize, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let count = connection.query_row(
+            "SELECT COUNT(*) FROM collection_items WHERE collection = ?1",
+            [collection.as_str()],
+            |row| row.get::<_, i64>(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    fn items(&self, collection: &Url, range: Range) -> Result<Vec<(u64, Url)>, StorageError> {
+        // cursors come from query strings, so they can be past what SQLite
+        // can hold
+        let cursor = |position: u64| i64::try_from(position).unwrap_or(i64::MAX);
+        let max_id = range.max_id.map_or(i64::MAX, cursor);
+        let min_id = range.min_id.map_or(0, cursor);
+        // a negative limit is no limit
+        let limit = range.limit.map_or(-1, |limit| limit as i64);
+        let order = if range.near_min_id() { "ASC" } else { "DESC" };
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(&format!(
+            "SELECT position, item FROM collection_items
+                WHERE collection = ?1 AND position > ?2 AND position < ?3
+                ORDER BY position {} LIMIT ?4",
+            order
+        ))?;
+        let rows = statement
+            .query_map(params![collection.as_str(), min_id, max_id, limit], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let (position, item) = row?;
+            items.push((
+                position as u64,
+                Url::parse(&item).map_err(StorageError::Url)?,
+            ));
+        }
+        if range.near_min_id() {
+            items.reverse();
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use smacktivity_network::webfinger::{
+    Jrd, JrdLink, ACTIVITY_MEDIA_TYPE, JRD_MEDIA_TYPE, PROFILE_PAGE_REL,
+};
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(serde::Deserialize)]
+pub struct WebFingerQuery {
+    resource: Option<String>,
+}
+
+/// `GET /.well-known/webfinger`
+pub async fn webfinger(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WebFingerQuery>,
+) -> Response {
+    let resource = match query.resource {
+        Some(resource) => resource,
+        None => return (StatusCode::BAD_REQUEST, "missing resource").into_response(),
+    };
+
+    let config = &state.config;
+    let acct = config.acct();
+    let actor_id = config.actor_id();
+    let profile_url = config.profile_url();
+
+    let known = resource.eq_ignore_ascii_case(&acct)
+        || resource == actor_id.as_str()
+        || resource == profile_url.as_str();
+    if !known {
+        return (StatusCode::NOT_FOUND, "no such resource").into_response();
+    }
+
+    let jrd = Jrd {
+        subject: acct,
+        aliases: vec![
+            String::from(actor_id.as_str()),
+            String::from(profile_url.as_str()),
+        ],
+        properties: HashMap::new(),
+        links: vec![
+            JrdLink {
+                rel: String::from("self"),
+                type_: Some(String::from(ACTIVITY_MEDIA_TYPE)),
+                href: Some(String::from(actor_id.as_str())),
+                ..Default::default()
+            },
+            JrdLink {
+                rel: String::from(PROFILE_PAGE_REL),
+                type_: Some(String::from("text/html")),
+                href: Some(String::from(profile_url.as_str())),
+                ..Default::default()
+            },
+        ],
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, JRD_MEDIA_TYPE),
+            (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ],
+        serde_json::to_string(&jrd).unwrap(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn look_up(state: &Arc<AppState>, resource: Option<&str>) -> Response {
+        let query = WebFingerQuery {
+            resource: resource.map(String::from),
+        };
+        webfinger(State(state.clone()), Query(query)).await
+    }
+
+    #[tokio::test]
+    async fn finds_the_user() {
+        let state = testing::state();
+        for resource in [
+            "acct:alice@local.example",
+            "acct:Alice@LOCAL.example",
+            "https://local.example/users/alice",
+            "https://local.example/@alice",
+        ] {
+            let response = look_up(&state, Some(resource)).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", resource);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], JRD_MEDIA_TYPE);
+            assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+            let jrd: Jrd = serde_json::from_value(testing::json(response).await).unwrap();
+            assert_eq!(jrd.subject, "acct:alice@local.example");
+            assert_eq!(jrd.actor_link(), Some("https://local.example/users/alice"));
+            assert!(jrd
+                .aliases
+                .contains(&String::from("https://local.example/@alice")));
+        }
+    }
+
+    #[tokio::test]
+    async fn doesnt_find_anyone_else() {
+        let state = testing::state();
+        for resource in [
+            "acct:bob@local.example",
+            "acct:alice@remote.example",
+            "https://local.example/users/bob",
+            "https://remote.example/users/alice",
+            "alice",
+        ] {
+            let response = look_up(&state, Some(resource)).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", resource);
+        }
+        assert_eq!(
+            look_up(&state, None).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}